@@ -0,0 +1,186 @@
+#![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
+#![allow(clippy::multiple_crate_versions)]
+//! Derive macro for the [`preferences`](https://docs.rs/preferences) crate.
+//!
+//! Don't depend on this crate directly: enable the `derive` feature of `preferences` instead,
+//! which re-exports the macro next to the `Preferences` trait.
+#![warn(missing_docs)]
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, Data, DeriveInput, Error, Expr, Fields, LitStr};
+
+/// Generates `load_default_key()` and `save_default_key()` methods that don't need an app or a
+/// key, for a type saved under a fixed key.
+///
+/// They're named so as not to hide the `load` and `save` methods of the `Preferences` trait,
+/// which stay available for saving under other keys.
+///
+/// The `#[prefs(app = "...", key = "...")]` attribute is required: `app` is the path of an
+/// `AppInfo` constant, and `key` the key to save under. The type must also implement `Serialize`
+/// and `Deserialize`.
+///
+/// Fields may have a `#[prefs(default = "...")]` attribute giving an expression for their default
+/// value. If any field has one, an `impl Default` is also generated (using `Default::default()`
+/// for the other fields), and `load_default_key()` returns that default when nothing has been
+/// saved yet. Otherwise, `load_default_key()` fails like `Preferences::load` in that case.
+///
+/// (Requires the `derive` feature.)
+///
+/// ```
+/// use preferences::{AppInfo, Preferences};
+/// use serde::{Deserialize, Serialize};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// #[derive(Preferences, Serialize, Deserialize)]
+/// #[prefs(app = "APP_INFO", key = "tests/docs/derive")]
+/// struct Graphics {
+///     #[prefs(default = "1920")]
+///     width: u32,
+///     fullscreen: bool,
+/// }
+///
+/// let mut graphics = Graphics::load_default_key().unwrap();
+/// graphics.fullscreen = true;
+/// graphics.save_default_key().unwrap();
+/// assert_eq!(Graphics::PREFERENCES_KEY, "tests/docs/derive");
+///
+/// // The trait methods still work for other keys.
+/// graphics.save(&APP_INFO, "tests/docs/derive-backup").unwrap();
+/// assert!(Graphics::load(&APP_INFO, "tests/docs/derive-backup").unwrap().fullscreen);
+/// ```
+#[proc_macro_derive(Preferences, attributes(prefs))]
+pub fn derive_preferences(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input)
+        .unwrap_or_else(Error::into_compile_error)
+        .into()
+}
+
+fn expand(input: &DeriveInput) -> syn::Result<TokenStream2> {
+    let mut app = None;
+    let mut key = None;
+    for attr in input
+        .attrs
+        .iter()
+        .filter(|attr| attr.path().is_ident("prefs"))
+    {
+        attr.parse_nested_meta(|meta| {
+            if meta.path.is_ident("app") {
+                app = Some(meta.value()?.parse::<LitStr>()?.parse::<Expr>()?);
+            } else if meta.path.is_ident("key") {
+                key = Some(meta.value()?.parse::<LitStr>()?);
+            } else {
+                return Err(meta.error("expected `app` or `key`"));
+            }
+            Ok(())
+        })?;
+    }
+    let app = app.ok_or_else(|| missing_attr(input, "app"))?;
+    let key = key.ok_or_else(|| missing_attr(input, "key"))?;
+
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let default = default_value(&input.data)?;
+    let default_impl = default.as_ref().map(|default| {
+        quote! {
+            impl #impl_generics ::core::default::Default for #name #ty_generics #where_clause {
+                fn default() -> Self {
+                    #default
+                }
+            }
+        }
+    });
+    let load = if default.is_some() {
+        quote! {
+            match <Self as ::preferences::Preferences>::load(&#app, #key) {
+                ::core::result::Result::Err(e) if e.is_not_found() => {
+                    ::core::result::Result::Ok(::core::default::Default::default())
+                }
+                result => result,
+            }
+        }
+    } else {
+        quote! {
+            <Self as ::preferences::Preferences>::load(&#app, #key)
+        }
+    };
+
+    Ok(quote! {
+        impl #impl_generics #name #ty_generics #where_clause {
+            /// Key this type is saved under.
+            pub const PREFERENCES_KEY: &'static str = #key;
+
+            /// Loads the value saved under `PREFERENCES_KEY`.
+            ///
+            /// # Errors
+            /// If a deserialization or file I/O error occurs.
+            pub fn load_default_key(
+            ) -> ::core::result::Result<Self, ::preferences::PreferencesError> {
+                #load
+            }
+
+            /// Saves this value under `PREFERENCES_KEY`.
+            ///
+            /// # Errors
+            /// If a serialization or file I/O error occurs.
+            pub fn save_default_key(
+                &self,
+            ) -> ::core::result::Result<(), ::preferences::PreferencesError> {
+                ::preferences::Preferences::save(self, &#app, #key)
+            }
+        }
+
+        #default_impl
+    })
+}
+
+fn missing_attr(input: &DeriveInput, name: &str) -> Error {
+    let msg = format!("missing `#[prefs({name} = \"...\")]` attribute");
+    Error::new_spanned(&input.ident, msg)
+}
+
+/// Returns an expression building the default value from the fields' `default` attributes, or
+/// `None` if no field has one.
+fn default_value(data: &Data) -> syn::Result<Option<TokenStream2>> {
+    let fields = match *data {
+        Data::Struct(ref data) => &data.fields,
+        _ => return Ok(None),
+    };
+    let mut any_default = false;
+    let mut values = Vec::new();
+    for field in fields {
+        let mut default = None;
+        for attr in field
+            .attrs
+            .iter()
+            .filter(|attr| attr.path().is_ident("prefs"))
+        {
+            attr.parse_nested_meta(|meta| {
+                if meta.path.is_ident("default") {
+                    default = Some(meta.value()?.parse::<LitStr>()?.parse::<Expr>()?);
+                    Ok(())
+                } else {
+                    Err(meta.error("expected `default`"))
+                }
+            })?;
+        }
+        any_default |= default.is_some();
+        values.push(default.map_or_else(
+            || quote!(::core::default::Default::default()),
+            |default| quote!(#default),
+        ));
+    }
+    if !any_default {
+        return Ok(None);
+    }
+    Ok(Some(match *fields {
+        Fields::Named(ref fields) => {
+            let idents = fields.named.iter().map(|field| &field.ident);
+            quote!(Self { #(#idents: #values),* })
+        }
+        Fields::Unnamed(_) => quote!(Self(#(#values),*)),
+        Fields::Unit => quote!(Self),
+    }))
+}
@@ -0,0 +1,167 @@
+use crate::PreferencesError;
+use clap::parser::ValueSource;
+use clap::{Arg, ArgAction, ArgMatches, Command};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Command-line arguments overriding fields of loaded preferences.
+///
+/// Each argument given explicitly on the command line overrides the field with the same name as
+/// the argument's id. Arguments left at their default value don't, so defaults declared for
+/// `clap` never hide saved preferences. Values are parsed as JSON, and anything that isn't valid
+/// JSON is treated as a plain string; arguments that can take several values (see
+/// `Arg::num_args` and `ArgAction::Append`) become arrays, even when given a single value.
+///
+/// String fields are overridden with the plain argument, so `--name 123` sets `name` to
+/// `"123"` rather than to a number. Where the type of a field can't be told from its current
+/// value (`null`, or an empty array), the plain argument is used if the parsed one doesn't fit.
+///
+/// Only fields present in the serialized preferences can be overridden, so arguments that aren't
+/// preferences (like a `--save` flag) are ignored.
+///
+/// (Requires the `clap` feature.)
+///
+/// ```
+/// use clap::{Arg, ArgAction, Command};
+/// use preferences::{AppInfo, ArgOverrides, Preferences};
+/// use serde::{Deserialize, Serialize};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// #[derive(Default, Serialize, Deserialize)]
+/// struct Settings {
+///     volume: u32,
+///     verbose: bool,
+/// }
+///
+/// let command = Command::new("tool")
+///     .arg(Arg::new("volume").long("volume"))
+///     .arg(Arg::new("verbose").long("verbose").action(ArgAction::SetTrue))
+///     .arg(Arg::new("save").long("save").action(ArgAction::SetTrue));
+/// let matches = command.clone().get_matches_from(["tool", "--volume", "7", "--save"]);
+///
+/// let key = "tests/docs/args";
+/// let saved = Settings::load_or_default(&APP_INFO, key).unwrap();
+/// let settings = ArgOverrides::from_matches(&command, &matches).apply(&saved).unwrap();
+/// assert_eq!(settings.volume, 7);
+/// if matches.get_flag("save") {
+///     settings.save(&APP_INFO, key).unwrap();
+/// }
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct ArgOverrides {
+    fields: Map<String, Value>,
+    plain: Map<String, Value>,
+}
+
+impl ArgOverrides {
+    /// Collects the arguments given explicitly on the command line in `matches`, as defined by
+    /// `command`.
+    ///
+    /// Values that aren't valid UTF-8 are skipped.
+    #[must_use]
+    pub fn from_matches(command: &Command, matches: &ArgMatches) -> Self {
+        let mut overrides = Self::default();
+        for id in matches.ids() {
+            let id = id.as_str();
+            if matches.value_source(id) != Some(ValueSource::CommandLine) {
+                continue;
+            }
+            let mut values: Vec<&str> = match matches.try_get_raw(id) {
+                Ok(Some(raw)) => raw.filter_map(|value| value.to_str()).collect(),
+                _ => continue,
+            };
+            let multiple = command
+                .get_arguments()
+                .find(|arg| arg.get_id() == id)
+                .map_or(false, takes_multiple);
+            let (value, plain) = if multiple {
+                (
+                    values.iter().map(|value| parse(value)).collect(),
+                    values.iter().map(|&value| Value::from(value)).collect(),
+                )
+            } else {
+                match values.pop() {
+                    Some(value) => (parse(value), Value::from(value)),
+                    None => continue,
+                }
+            };
+            overrides.fields.insert(id.into(), value);
+            overrides.plain.insert(id.into(), plain);
+        }
+        overrides
+    }
+
+    /// Returns the fields to override and their new values.
+    #[must_use]
+    pub const fn fields(&self) -> &Map<String, Value> {
+        &self.fields
+    }
+
+    /// Returns a copy of `value` with its fields overridden.
+    ///
+    /// # Errors
+    /// If `value` can't be serialized, or if the result can't be deserialized (e.g. because an
+    /// argument has the wrong type for its field).
+    pub fn apply<T: Serialize + DeserializeOwned>(&self, value: &T) -> Result<T, PreferencesError> {
+        let mut value = serde_json::to_value(value)?;
+        let mut untyped = Vec::new();
+        if let Value::Object(ref mut fields) = value {
+            for (name, field) in fields {
+                let (new, plain) = match (self.fields.get(name), self.plain.get(name)) {
+                    (Some(new), Some(plain)) => (new, plain),
+                    _ => continue,
+                };
+                if holds_strings(field) {
+                    *field = plain.clone();
+                    continue;
+                }
+                if (field.is_null() || field.as_array().map_or(false, Vec::is_empty))
+                    && new != plain
+                {
+                    untyped.push(name.clone());
+                }
+                *field = new.clone();
+            }
+        }
+        let error = match serde_json::from_value(value.clone()) {
+            Ok(value) => return Ok(value),
+            Err(e) => e,
+        };
+        // Fields whose type can't be told from their value may be strings after all: try every
+        // combination of them as plain arguments.
+        untyped.truncate(8);
+        for combination in 1..1_u32 << untyped.len() {
+            let mut value = value.clone();
+            for (i, name) in untyped.iter().enumerate() {
+                if combination & 1 << i != 0 {
+                    value[name] = self.plain[name].clone();
+                }
+            }
+            if let Ok(value) = serde_json::from_value(value) {
+                return Ok(value);
+            }
+        }
+        Err(error.into())
+    }
+}
+
+/// Returns `true` if `arg` can take several values, whether in one occurrence or across several.
+fn takes_multiple(arg: &Arg) -> bool {
+    matches!(arg.get_action(), ArgAction::Append)
+        || arg
+            .get_num_args()
+            .map_or(false, |range| range.max_values() > 1)
+}
+
+/// Returns `true` if `value` is a string, or an array holding one.
+fn holds_strings(value: &Value) -> bool {
+    value.is_string()
+        || value
+            .as_array()
+            .map_or(false, |values| values.iter().any(Value::is_string))
+}
+
+fn parse(value: &str) -> Value {
+    serde_json::from_str(value).unwrap_or_else(|_| Value::String(value.into()))
+}
@@ -0,0 +1,261 @@
+use crate::{Backend, Capabilities, FileBackend, Preferences, PreferencesError};
+use app_dirs::AppInfo;
+use blocking::unblock;
+use futures_lite::future::block_on;
+use futures_lite::io::{AsyncRead, AsyncReadExt, AsyncWrite, AsyncWriteExt};
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use std::future::Future;
+use std::pin::Pin;
+use std::sync::Arc;
+
+/// Boxed future returned by the methods of [`AsyncPreferences`](trait.AsyncPreferences.html).
+pub type PreferencesFuture<'a, T> =
+    Pin<Box<dyn Future<Output = Result<T, PreferencesError>> + Send + 'a>>;
+
+/// Asynchronous counterpart of [`Preferences`](trait.Preferences.html), for use from async
+/// runtimes. (Requires the `async` feature.)
+///
+/// This trait doesn't depend on any particular executor: file I/O runs on a separate thread pool
+/// (from the `blocking` crate) so it never stalls the caller's runtime, and the stream variants
+/// use the `futures` I/O traits. (Use `tokio-util`'s `compat` module to pass tokio streams.)
+/// Like `Preferences`, this trait is automatically implemented for any type that implements
+/// `Serialize` and `Deserialize`.
+///
+/// ```
+/// use preferences::{AppInfo, AsyncPreferences};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// # futures_lite::future::block_on(async {
+/// let key = "tests/docs/async";
+/// vec![1, 2, 3].save_async(&APP_INFO, key).await.unwrap();
+/// let loaded = Vec::<i32>::load_async(&APP_INFO, key).await.unwrap();
+/// assert_eq!(loaded, [1, 2, 3]);
+/// # });
+/// ```
+pub trait AsyncPreferences: Preferences + Send + 'static {
+    /// Same as `Preferences::save`, but asynchronous.
+    ///
+    /// The value is serialized immediately, so later changes to it don't affect what is saved.
+    ///
+    /// # Errors
+    /// If a serialization or file I/O error (e.g. permission denied) occurs.
+    fn save_async<S: AsRef<str>>(&self, app: &AppInfo, key: S) -> PreferencesFuture<'static, ()>;
+    /// Same as `Preferences::load`, but asynchronous.
+    ///
+    /// # Errors
+    /// If a deserialization or file I/O error (e.g. permission denied) occurs, or if no user data
+    /// exists for that `key`.
+    fn load_async<S: AsRef<str>>(app: &AppInfo, key: S) -> PreferencesFuture<'static, Self>;
+    /// Same as `save_async`, but writes the serialized preferences to an arbitrary async writer.
+    ///
+    /// # Errors
+    /// If a write or serialization error occurs.
+    fn save_to_async<'a, W>(&self, writer: &'a mut W) -> PreferencesFuture<'a, ()>
+    where
+        W: AsyncWrite + Unpin + Send;
+    /// Same as `load_async`, but reads the serialized preferences from an arbitrary async reader.
+    ///
+    /// # Errors
+    /// If a read or deserialization error occurs.
+    fn load_from_async<R>(reader: &mut R) -> PreferencesFuture<'_, Self>
+    where
+        R: AsyncRead + Unpin + Send;
+    /// Same as `save_async`, but writes the serialized preferences to an arbitrary
+    /// [`AsyncBackend`](trait.AsyncBackend.html).
+    ///
+    /// # Errors
+    /// If a serialization error occurs, or if the backend fails to write the data.
+    fn save_in_async<'a, B, S>(
+        &self,
+        backend: &'a B,
+        app: &'a AppInfo,
+        key: S,
+    ) -> PreferencesFuture<'a, ()>
+    where
+        B: AsyncBackend + ?Sized,
+        S: AsRef<str>,
+    {
+        let data = self.serialized();
+        let key = key.as_ref().to_owned();
+        Box::pin(async move { backend.write(app, &key, &data?).await })
+    }
+    /// Same as `load_async`, but reads the serialized preferences from an arbitrary
+    /// [`AsyncBackend`](trait.AsyncBackend.html).
+    ///
+    /// # Errors
+    /// If a deserialization error occurs, or if the backend fails to read the data.
+    fn load_in_async<'a, B, S>(
+        backend: &'a B,
+        app: &'a AppInfo,
+        key: S,
+    ) -> PreferencesFuture<'a, Self>
+    where
+        B: AsyncBackend + ?Sized,
+        S: AsRef<str>,
+    {
+        let key = key.as_ref().to_owned();
+        Box::pin(async move {
+            let data = backend.read(app, &key).await?;
+            Self::load_from(&mut data.as_slice())
+        })
+    }
+}
+
+/// Asynchronous counterpart of [`Backend`](trait.Backend.html), for storage that is naturally
+/// asynchronous (e.g. network or database services). (Requires the `async` feature.)
+///
+/// Use [`BlockOn`](struct.BlockOn.html) to use an async backend from synchronous code, and
+/// [`Unblock`](struct.Unblock.html) to use a synchronous backend from async code.
+pub trait AsyncBackend: Send + Sync {
+    /// Reads the serialized data stored under `key`.
+    ///
+    /// # Errors
+    /// If the data can't be read. A key with no stored data is reported as a
+    /// [`PreferencesError::Io`](enum.PreferencesError.html) with kind `NotFound`.
+    fn read<'a>(&'a self, app: &'a AppInfo, key: &'a str) -> PreferencesFuture<'a, Vec<u8>>;
+    /// Replaces the serialized data stored under `key`.
+    ///
+    /// # Errors
+    /// If the data can't be written.
+    fn write<'a>(
+        &'a self,
+        app: &'a AppInfo,
+        key: &'a str,
+        data: &'a [u8],
+    ) -> PreferencesFuture<'a, ()>;
+    /// Describes what this backend guarantees, so generic code can adapt to it.
+    ///
+    /// The default implementation promises nothing.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// Adapter implementing [`Backend`](trait.Backend.html) for an
+/// [`AsyncBackend`](trait.AsyncBackend.html), by blocking the current thread until each
+/// operation completes. (Requires the `async` feature.)
+#[derive(Clone, Debug, Default)]
+pub struct BlockOn<B> {
+    inner: B,
+}
+
+impl<B: AsyncBackend> BlockOn<B> {
+    /// Wraps `backend` for use from synchronous code.
+    #[must_use]
+    pub const fn new(backend: B) -> Self {
+        Self { inner: backend }
+    }
+
+    /// Unwraps this adapter, returning the underlying backend.
+    #[must_use]
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: AsyncBackend> Backend for BlockOn<B> {
+    fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        block_on(self.inner.read(app, key))
+    }
+    fn write(&self, app: &AppInfo, key: &str, data: &[u8]) -> Result<(), PreferencesError> {
+        block_on(self.inner.write(app, key, data))
+    }
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+}
+
+/// Adapter implementing [`AsyncBackend`](trait.AsyncBackend.html) for a
+/// [`Backend`](trait.Backend.html), by running each operation on a separate thread pool.
+/// (Requires the `async` feature.)
+#[derive(Debug, Default)]
+pub struct Unblock<B> {
+    inner: Arc<B>,
+}
+
+impl<B> Clone for Unblock<B> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<B: Backend + Send + Sync + 'static> Unblock<B> {
+    /// Wraps `backend` for use from async code.
+    #[must_use]
+    pub fn new(backend: B) -> Self {
+        Self {
+            inner: Arc::new(backend),
+        }
+    }
+}
+
+impl<B: Backend + Send + Sync + 'static> AsyncBackend for Unblock<B> {
+    fn read<'a>(&'a self, app: &'a AppInfo, key: &'a str) -> PreferencesFuture<'a, Vec<u8>> {
+        let inner = Arc::clone(&self.inner);
+        let app = app.clone();
+        let key = key.to_owned();
+        Box::pin(unblock(move || inner.read(&app, &key)))
+    }
+    fn write<'a>(
+        &'a self,
+        app: &'a AppInfo,
+        key: &'a str,
+        data: &'a [u8],
+    ) -> PreferencesFuture<'a, ()> {
+        let inner = Arc::clone(&self.inner);
+        let app = app.clone();
+        let key = key.to_owned();
+        let data = data.to_vec();
+        Box::pin(unblock(move || inner.write(&app, &key, &data)))
+    }
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+}
+
+impl<T> AsyncPreferences for T
+where
+    T: Serialize + DeserializeOwned + Send + 'static,
+{
+    fn save_async<S: AsRef<str>>(&self, app: &AppInfo, key: S) -> PreferencesFuture<'static, ()> {
+        let data = self.serialized();
+        let app = app.clone();
+        let key = key.as_ref().to_owned();
+        Box::pin(async move {
+            let data = data?;
+            unblock(move || FileBackend::new().write(&app, &key, &data)).await
+        })
+    }
+    fn load_async<S: AsRef<str>>(app: &AppInfo, key: S) -> PreferencesFuture<'static, Self> {
+        let app = app.clone();
+        let key = key.as_ref().to_owned();
+        Box::pin(async move {
+            let data = unblock(move || FileBackend::new().read(&app, &key)).await?;
+            Self::load_from(&mut data.as_slice())
+        })
+    }
+    fn save_to_async<'a, W>(&self, writer: &'a mut W) -> PreferencesFuture<'a, ()>
+    where
+        W: AsyncWrite + Unpin + Send,
+    {
+        let data = self.serialized();
+        Box::pin(async move {
+            writer.write_all(&data?).await?;
+            writer.flush().await?;
+            Ok(())
+        })
+    }
+    fn load_from_async<R>(reader: &mut R) -> PreferencesFuture<'_, Self>
+    where
+        R: AsyncRead + Unpin + Send,
+    {
+        Box::pin(async move {
+            let mut data = Vec::new();
+            reader.read_to_end(&mut data).await?;
+            Self::load_from(&mut data.as_slice())
+        })
+    }
+}
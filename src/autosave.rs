@@ -0,0 +1,283 @@
+use crate::{Backend, FileBackend, Flush, Preferences, PreferencesError};
+use app_dirs::AppInfo;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError};
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+const FLUSH_POLL_INTERVAL: Duration = Duration::from_millis(100);
+
+struct State<T> {
+    value: T,
+    dirty: bool,
+    saving: bool,
+    flushing: usize,
+    stopping: bool,
+    error: Option<PreferencesError>,
+}
+
+struct Shared<T> {
+    state: Mutex<State<T>>,
+    changed: Condvar,
+}
+
+impl<T> Shared<T> {
+    fn lock(&self) -> MutexGuard<'_, State<T>> {
+        self.state.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+/// Wrapper that saves a value automatically whenever it changes, at most once per interval.
+///
+/// Changes made through [`lock`](#method.lock) are picked up by a background thread, which saves
+/// the first change right away and then waits for `interval` before saving again, so bursts of
+/// changes (e.g. dragging a slider) turn into a handful of writes. The latest value is always
+/// saved eventually, and pending changes are saved when the wrapper is dropped.
+///
+/// ```
+/// use preferences::{AppInfo, AutoSave, Preferences};
+/// use std::time::Duration;
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let key = "tests/docs/autosave";
+/// let volume = AutoSave::new(0.5, &APP_INFO, key, Duration::from_millis(250));
+/// for _ in 0..10 {
+///     *volume.lock() += 0.05;
+/// }
+/// drop(volume);
+/// assert!((f64::load(&APP_INFO, key).unwrap() - 1.0).abs() < 1e-9);
+/// ```
+pub struct AutoSave<T> {
+    shared: Arc<Shared<T>>,
+    app: AppInfo,
+    key: String,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl<T: Preferences + Send + 'static> AutoSave<T> {
+    /// Wraps `value`, saving it under `key` in the default
+    /// [`FileBackend`](struct.FileBackend.html) whenever it changes.
+    #[must_use]
+    pub fn new<S: AsRef<str>>(value: T, app: &AppInfo, key: S, interval: Duration) -> Self {
+        Self::with_backend(value, FileBackend::new(), app, key, interval)
+    }
+
+    /// Same as `new`, but saves to `backend`.
+    #[must_use]
+    pub fn with_backend<B, S>(
+        value: T,
+        backend: B,
+        app: &AppInfo,
+        key: S,
+        interval: Duration,
+    ) -> Self
+    where
+        B: Backend + Send + 'static,
+        S: AsRef<str>,
+    {
+        let shared = Arc::new(Shared {
+            state: Mutex::new(State {
+                value,
+                dirty: false,
+                saving: false,
+                flushing: 0,
+                stopping: false,
+                error: None,
+            }),
+            changed: Condvar::new(),
+        });
+        let worker = {
+            let shared = Arc::clone(&shared);
+            let app = app.clone();
+            let key = key.as_ref().to_owned();
+            thread::spawn(move || run(&shared, &backend, &app, &key, interval))
+        };
+        Self {
+            shared,
+            app: app.clone(),
+            key: key.as_ref().into(),
+            worker: Some(worker),
+        }
+    }
+}
+
+impl<T> AutoSave<T> {
+    /// Locks the value for reading or writing. Writing through the returned guard marks the value
+    /// as changed.
+    #[must_use]
+    pub fn lock(&self) -> AutoSaveGuard<'_, T> {
+        AutoSaveGuard {
+            state: self.shared.lock(),
+            changed: &self.shared.changed,
+            touched: false,
+        }
+    }
+
+    /// Returns the app this value is saved for.
+    #[must_use]
+    pub const fn app(&self) -> &AppInfo {
+        &self.app
+    }
+
+    /// Returns the key this value is saved under.
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Saves any pending change right away, without waiting for the rest of the interval, and
+    /// waits until it's written.
+    ///
+    /// # Errors
+    /// Returns and clears the first error that occurred while saving in the background, like
+    /// [`take_error`](#method.take_error).
+    pub fn flush(&self) -> Result<(), PreferencesError> {
+        let mut state = self.shared.lock();
+        state.flushing += 1;
+        self.shared.changed.notify_all();
+        // The worker only stops early if the backend panicked.
+        let running = || {
+            self.worker
+                .as_ref()
+                .map_or(false, |worker| !worker.is_finished())
+        };
+        while (state.dirty || state.saving) && running() {
+            state = self
+                .shared
+                .changed
+                .wait_timeout(state, FLUSH_POLL_INTERVAL)
+                .unwrap_or_else(PoisonError::into_inner)
+                .0;
+        }
+        state.flushing -= 1;
+        state.error.take().map_or(Ok(()), Err)
+    }
+
+    /// Returns and clears the first error that occurred while saving in the background since
+    /// the last call.
+    #[must_use]
+    pub fn take_error(&self) -> Option<PreferencesError> {
+        self.shared.lock().error.take()
+    }
+}
+
+impl<T> Flush for AutoSave<T> {
+    fn flush(&self) -> Result<(), PreferencesError> {
+        Self::flush(self)
+    }
+}
+
+impl<T> Drop for AutoSave<T> {
+    fn drop(&mut self) {
+        self.shared.lock().stopping = true;
+        self.shared.changed.notify_all();
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AutoSave<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("AutoSave")
+            .field("value", &self.shared.lock().value)
+            .field("app", &self.app)
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
+
+/// Guard giving access to the value inside an [`AutoSave`](struct.AutoSave.html).
+///
+/// The value is saved in the background once the guard is dropped, if it was accessed mutably.
+pub struct AutoSaveGuard<'a, T> {
+    state: MutexGuard<'a, State<T>>,
+    changed: &'a Condvar,
+    touched: bool,
+}
+
+impl<T> Deref for AutoSaveGuard<'_, T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.state.value
+    }
+}
+
+impl<T> DerefMut for AutoSaveGuard<'_, T> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.touched = true;
+        &mut self.state.value
+    }
+}
+
+impl<T> Drop for AutoSaveGuard<'_, T> {
+    fn drop(&mut self) {
+        if self.touched {
+            self.state.dirty = true;
+            self.changed.notify_all();
+        }
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for AutoSaveGuard<'_, T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        self.state.value.fmt(f)
+    }
+}
+
+fn run<T: Preferences, B: Backend>(
+    shared: &Shared<T>,
+    backend: &B,
+    app: &AppInfo,
+    key: &str,
+    interval: Duration,
+) {
+    while let Some(data) = next_change(shared) {
+        let result = data.and_then(|data| backend.write(app, key, &data));
+        rest(shared, result, interval);
+    }
+}
+
+/// Waits for the value to change, returning it serialized, or `None` once the wrapper is dropped
+/// with nothing left to save.
+fn next_change<T: Preferences>(shared: &Shared<T>) -> Option<Result<Vec<u8>, PreferencesError>> {
+    let mut state = shared.lock();
+    while !state.dirty && !state.stopping {
+        state = shared
+            .changed
+            .wait(state)
+            .unwrap_or_else(PoisonError::into_inner);
+    }
+    if !state.dirty {
+        return None;
+    }
+    state.dirty = false;
+    state.saving = true;
+    Some(state.value.serialized())
+}
+
+/// Records the outcome of a save, then waits out the rest of the interval (unless the wrapper
+/// is dropped or flushed first).
+fn rest<T>(shared: &Shared<T>, result: Result<(), PreferencesError>, interval: Duration) {
+    let saved_at = Instant::now();
+    let mut state = shared.lock();
+    if let Err(e) = result {
+        state.error.get_or_insert(e);
+    }
+    state.saving = false;
+    shared.changed.notify_all();
+    while !state.stopping && state.flushing == 0 {
+        let remaining = interval.saturating_sub(saved_at.elapsed());
+        if remaining.is_zero() {
+            break;
+        }
+        state = shared
+            .changed
+            .wait_timeout(state, remaining)
+            .unwrap_or_else(PoisonError::into_inner)
+            .0;
+    }
+    drop(state);
+}
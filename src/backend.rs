@@ -0,0 +1,1432 @@
+use crate::defaults::BundledDefaults;
+use crate::events::{self, ChangeEvent};
+use crate::lock::{lock, LockMode};
+use crate::metadata::{crc32, ContentType, Metadata, Provenance, VersionHeader};
+#[cfg(feature = "signing")]
+use crate::signing::SigningKey;
+use crate::{Preferences, PreferencesError};
+use app_dirs::{get_app_dir, sanitized, AppDataType, AppDirsError, AppInfo};
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::ffi::OsString;
+use std::fs::{self, create_dir_all, File};
+use std::io::{self, ErrorKind, Read, Write};
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, SystemTime};
+
+static PREFS_FILE_EXTENSION: &str = ".prefs.json";
+static DEFAULT_PREFS_FILENAME: &str = "prefs.json";
+static METADATA_FILE_EXTENSION: &str = ".meta";
+static SHADOW_FILE_EXTENSION: &str = ".tmp";
+static BACKUP_FILE_EXTENSION: &str = ".bak";
+static CORRUPT_FILE_EXTENSION: &str = ".corrupt";
+static JOURNAL_FILE_EXTENSION: &str = ".journal";
+static EVENT_LOG_FILE_EXTENSION: &str = ".events.jsonl";
+static SNAPSHOTS_DIR_EXTENSION: &str = ".snapshots";
+static PROFILES_DIR_EXTENSION: &str = ".profiles";
+
+/// Environment variable that moves the default location, like
+/// [`FileBackend::with_base_dir`](struct.FileBackend.html#method.with_base_dir).
+pub static BASE_DIR_ENV_VAR: &str = "PREFERENCES_BASE_DIR";
+
+/// Storage location for serialized user data.
+///
+/// A backend maps an app and a preferences key to a blob of serialized data. The default backend,
+/// [`FileBackend`](struct.FileBackend.html), is the one used by
+/// [`Preferences::save`](trait.Preferences.html#tymethod.save) and
+/// [`Preferences::load`](trait.Preferences.html#tymethod.load). To read or write user data
+/// somewhere else, pass a backend to `save_in(..)` or `load_in(..)` instead.
+pub trait Backend {
+    /// Reads the serialized data stored under `key`.
+    ///
+    /// # Errors
+    /// If the data can't be read. A key with no stored data is reported as a
+    /// [`PreferencesError::Io`](enum.PreferencesError.html) with kind `NotFound`.
+    fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError>;
+    /// Replaces the serialized data stored under `key`.
+    ///
+    /// # Errors
+    /// If the data can't be written.
+    fn write(&self, app: &AppInfo, key: &str, data: &[u8]) -> Result<(), PreferencesError>;
+    /// Describes what this backend guarantees, so generic code can adapt to it.
+    ///
+    /// The default implementation promises nothing.
+    fn capabilities(&self) -> Capabilities {
+        Capabilities::default()
+    }
+}
+
+/// Guarantees offered by a [`Backend`](trait.Backend.html).
+///
+/// Every flag defaults to `false` (and the size limit to `None`), so a backend only needs to set
+/// what it actually supports:
+///
+/// ```
+/// use preferences::Capabilities;
+///
+/// let mut capabilities = Capabilities::default();
+/// capabilities.atomic = true;
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+#[allow(clippy::struct_excessive_bools)] // independent flags, not a state machine
+pub struct Capabilities {
+    /// A write either fully replaces the stored data or leaves it untouched; readers never see
+    /// partial data.
+    pub atomic: bool,
+    /// Changes to the stored data can be observed as they happen.
+    pub watchable: bool,
+    /// Several keys can be written together as a single all-or-nothing operation.
+    pub transactional: bool,
+    /// Stored data is encrypted by the backend itself.
+    pub encrypted_at_rest: bool,
+    /// Largest value, in bytes, that can be written, if the backend has such a limit.
+    pub max_value_size: Option<usize>,
+    /// What readers and crashes can observe while a value is being written. `atomic` is set
+    /// whenever this is at least `Consistency::Atomic`.
+    pub consistency: Consistency,
+}
+
+/// How much of a write other readers (or the next run after a crash) can observe, from weakest
+/// to strongest. Levels compare in that order, so generic code can check for a minimum:
+///
+/// ```
+/// use preferences::{Backend, Consistency, FileBackend};
+///
+/// assert!(FileBackend::new().capabilities().consistency >= Consistency::Atomic);
+/// ```
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+#[non_exhaustive]
+pub enum Consistency {
+    /// No guarantee: a reader running alongside a write may see partially written data.
+    Unspecified,
+    /// A reader sees either the old or the new data, never a mix of both.
+    Atomic,
+    /// Same as `Atomic`, and the new data has reached the disk by the time the write returns, so
+    /// it survives a crash or power loss.
+    Durable,
+}
+
+/// How a [`FileBackend`](struct.FileBackend.html) writes files.
+///
+/// The defaults favor speed. Change them for data that must survive a crash, like license
+/// activations or crash-recovery flags:
+///
+/// ```
+/// use preferences::{AppInfo, FileBackend, Preferences, SaveOptions};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let mut options = SaveOptions::default();
+/// options.sync = true;
+/// let backend = FileBackend::new().with_save_options(options);
+/// String::from("ABCD-1234").save_in(&backend, &APP_INFO, "tests/docs/license").unwrap();
+/// ```
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct SaveOptions {
+    /// Wait for each saved file (and, on Unix, its directory entry) to reach the disk before
+    /// returning, so that it survives a crash or power loss. This makes saving much slower.
+    pub sync: bool,
+}
+
+/// Previous version of a key's data, kept by a [`FileBackend`](struct.FileBackend.html)
+/// configured with [`with_backups`](struct.FileBackend.html#method.with_backups).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub struct Revision {
+    /// How many saves back this version is: 1 for the version before the current data, 2 for the
+    /// one before that, and so on. Pass it to
+    /// [`load_version`](struct.FileBackend.html#method.load_version).
+    pub number: usize,
+    /// When this version was saved, if the platform records it.
+    pub saved_at: Option<SystemTime>,
+}
+
+/// Backend storing each key as a JSON file in the active user's configuration directory.
+///
+/// The directory is decided by the `app_dirs` crate with the data type `UserConfig`. Within it,
+/// each key maps to a sanitized path ending in `.prefs.json`.
+#[derive(Clone, Debug)]
+pub struct FileBackend {
+    root: Root,
+    app_version: Option<String>,
+    lock_timeout: Option<Duration>,
+    backups: usize,
+    checksums: bool,
+    save_options: SaveOptions,
+    event_log: bool,
+    schema_version: Option<u32>,
+    #[cfg(feature = "signing")]
+    signing_key: Option<SigningKey>,
+    defaults: Option<Arc<BundledDefaults>>,
+}
+
+/// Whose preferences a [`FileBackend`](struct.FileBackend.html) stores.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Scope {
+    /// Preferences of the active user, in their configuration directory. This is the default.
+    User,
+    /// Machine-wide preferences shared by all users, such as admin-installed defaults. The
+    /// location is platform-specific (e.g. the first XDG config directory, usually `/etc/xdg`,
+    /// on Linux, and `ProgramData` on Windows), and is usually only writable by administrators.
+    System,
+}
+
+impl Scope {
+    const fn data_type(self) -> AppDataType {
+        match self {
+            Self::User => AppDataType::UserConfig,
+            Self::System => AppDataType::SharedConfig,
+        }
+    }
+}
+
+#[derive(Clone, Debug)]
+enum Root {
+    Platform(AppDataType),
+    Dir(PathBuf),
+}
+
+impl FileBackend {
+    /// Creates a backend for the active user's configuration directory.
+    ///
+    /// If the [`PREFERENCES_BASE_DIR`](static.BASE_DIR_ENV_VAR.html) environment variable is set,
+    /// files are stored under the directory it names instead, as with
+    /// [`with_base_dir`](#method.with_base_dir). This is also the backend behind
+    /// [`Preferences::save`](trait.Preferences.html#tymethod.save) and
+    /// [`Preferences::load`](trait.Preferences.html#tymethod.load), so setting the variable (for
+    /// the process, or for the ones it launches) moves the data of code that doesn't pick a
+    /// backend.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_scope(Scope::User)
+    }
+
+    /// Creates a backend for the configuration directory of the given scope.
+    #[must_use]
+    pub const fn with_scope(scope: Scope) -> Self {
+        Self {
+            root: Root::Platform(scope.data_type()),
+            app_version: None,
+            lock_timeout: None,
+            backups: 0,
+            checksums: false,
+            save_options: SaveOptions { sync: false },
+            event_log: false,
+            schema_version: None,
+            #[cfg(feature = "signing")]
+            signing_key: None,
+            defaults: None,
+        }
+    }
+
+    /// Creates a backend storing files under `dir` instead of the user's configuration
+    /// directory.
+    ///
+    /// Like the default location, `dir` is a base directory shared by all apps: files for each
+    /// app go in a subdirectory named after the app. This is useful for test harnesses,
+    /// sandboxed builds, and servers with their own data directory.
+    ///
+    /// To move the data of plain `save(..)` and `load(..)` calls too, set the
+    /// [`PREFERENCES_BASE_DIR`](static.BASE_DIR_ENV_VAR.html) environment variable instead.
+    #[must_use]
+    pub fn with_base_dir<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            root: Root::Dir(dir.into()),
+            ..Self::new()
+        }
+    }
+
+    /// Creates a backend for a "portable" installation, storing files in the directory of the
+    /// running executable instead of the user's profile.
+    ///
+    /// Files for each app go in a subdirectory named after the app.
+    ///
+    /// # Errors
+    /// If the path of the running executable can't be determined.
+    pub fn portable() -> Result<Self, PreferencesError> {
+        Self::portable_in("")
+    }
+
+    /// Same as `portable`, but stores files in `dir`, relative to the directory of the running
+    /// executable.
+    ///
+    /// # Errors
+    /// If the path of the running executable can't be determined.
+    pub fn portable_in<P: AsRef<Path>>(dir: P) -> Result<Self, PreferencesError> {
+        let exe = env::current_exe()?;
+        let exe_dir = exe.parent().ok_or(AppDirsError::NotSupported)?;
+        Ok(Self::with_base_dir(exe_dir.join(dir)))
+    }
+
+    /// Records a [`Provenance`](struct.Provenance.html) watermark (this app version, the crate
+    /// version, the time and a hash of the host name) in the metadata of every file saved.
+    #[must_use]
+    pub fn with_provenance<S: Into<String>>(mut self, app_version: S) -> Self {
+        self.app_version = Some(app_version.into());
+        self
+    }
+
+    /// Records a [`VersionHeader`](struct.VersionHeader.html) with every file saved, and refuses
+    /// to load files saved with a newer one.
+    ///
+    /// Bump `schema` whenever the layout of the app's data changes incompatibly. Loading a file
+    /// saved by a newer version of the app (or of this crate) then fails with
+    /// [`PreferencesError::UnsupportedVersion`](enum.PreferencesError.html#variant.UnsupportedVersion)
+    /// rather than silently misreading it. Files saved without a header are loaded as usual; use
+    /// [`metadata`](#method.metadata) to find out which version a file has. A backend without a
+    /// schema version keeps the header already recorded for a file when saving it.
+    ///
+    /// ```
+    /// use preferences::{AppInfo, FileBackend, Preferences, PreferencesError};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let key = "tests/docs/schema-version";
+    /// let newer = FileBackend::new().with_schema_version(3);
+    /// 42.save_in(&newer, &APP_INFO, key).unwrap();
+    /// assert_eq!(newer.metadata(&APP_INFO, key).unwrap().version.unwrap().schema, 3);
+    ///
+    /// let older = FileBackend::new().with_schema_version(2);
+    /// let result = i32::load_in(&older, &APP_INFO, key);
+    /// assert!(matches!(result, Err(PreferencesError::UnsupportedVersion(..))));
+    /// ```
+    #[must_use]
+    pub const fn with_schema_version(mut self, schema: u32) -> Self {
+        self.schema_version = Some(schema);
+        self
+    }
+
+    /// Saves `data` under `key` as-is, without serializing it, and records its content type in
+    /// the key's [`Metadata`](struct.Metadata.html).
+    ///
+    /// This lets data that doesn't go through `serde` (certificates, scripts, images...) live in
+    /// the same managed tree as the rest of the app's preferences.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs.
+    pub fn save_raw<D: AsRef<[u8]>>(
+        &self,
+        app: &AppInfo,
+        key: &str,
+        data: D,
+        content_type: ContentType,
+    ) -> Result<(), PreferencesError> {
+        self.write_tagged(app, key, data.as_ref(), Some(content_type))
+    }
+
+    /// Loads the data saved under `key` as-is, without deserializing it.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs, or if no data exists for that `key`.
+    pub fn load_raw(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        self.read(app, key)
+    }
+
+    fn write_tagged(
+        &self,
+        app: &AppInfo,
+        key: &str,
+        data: &[u8],
+        content_type: Option<ContentType>,
+    ) -> Result<(), PreferencesError> {
+        let path = self.path(app, key)?;
+        path.parent().map(create_dir_all);
+        self.write_at(app, &path, key, data, content_type)
+    }
+
+    /// Writes the data of `key` to `path`, which must be `self.path(app, key)` and have an
+    /// existing parent directory.
+    pub(crate) fn write_at(
+        &self,
+        app: &AppInfo,
+        path: &Path,
+        key: &str,
+        data: &[u8],
+        content_type: Option<ContentType>,
+    ) -> Result<(), PreferencesError> {
+        let _lock = self.lock(path, key, LockMode::Exclusive)?;
+        // Without a schema version of its own, keep the one the file was migrated to, so that it
+        // isn't migrated again from version 1.
+        let version = match self.schema_version {
+            Some(schema) => Some(VersionHeader::current(schema)),
+            None => recorded_version(path)?,
+        };
+        rotate_metadata_backups(path, self.backups)?;
+        write_atomic(path, data, self.backups, self.save_options.sync)?;
+        let metadata = Metadata {
+            provenance: self.app_version.as_deref().map(Provenance::current),
+            content_type,
+            checksum: self.checksums.then(|| crc32(data)),
+            signature: self.signature(key, data),
+            version,
+        };
+        write_metadata(path, &metadata, self.save_options.sync)?;
+        self.log_event(app, key, Some(data))
+    }
+
+    /// Reads the data of `key` from `path`, which must be `self.path(app, key)`.
+    pub(crate) fn read_at(&self, path: &Path, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        let mut data = Vec::new();
+        let mut file = match (File::open(path), self.default_data(key)) {
+            (Err(e), Some(default)) if e.kind() == ErrorKind::NotFound => {
+                return Ok(default.as_bytes().to_vec());
+            }
+            (result, _) => result?,
+        };
+        let _lock = self.lock(path, key, LockMode::Shared)?;
+        file.read_to_end(&mut data)?;
+        if self.checksums {
+            verify_checksum(path, key, &data)?;
+        }
+        if let Some(schema) = self.schema_version {
+            check_version(path, key, schema)?;
+        }
+        #[cfg(feature = "signing")]
+        if let Some(ref secret) = self.signing_key {
+            verify_signature(path, key, &data, secret)?;
+        }
+        Ok(data)
+    }
+
+    /// Takes the exclusive lock guarding `path` for as long as the returned file is kept open,
+    /// waiting up to the lock timeout, if any. Returns a copy of this backend that doesn't lock
+    /// `path` again while it's held.
+    pub(crate) fn hold_lock(
+        &self,
+        path: &Path,
+        key: &str,
+    ) -> Result<(Self, File), PreferencesError> {
+        let file = lock(path, key, LockMode::Exclusive, self.lock_timeout)?;
+        let backend = Self {
+            lock_timeout: None,
+            ..self.clone()
+        };
+        Ok((backend, file))
+    }
+
+    /// Records a change in the event log, if enabled.
+    fn log_event(
+        &self,
+        app: &AppInfo,
+        key: &str,
+        data: Option<&[u8]>,
+    ) -> Result<(), PreferencesError> {
+        if !self.event_log {
+            return Ok(());
+        }
+        let event = ChangeEvent::now(key, data, self.app_version.as_deref());
+        events::append(&self.app_file(app, EVENT_LOG_FILE_EXTENSION)?, &event)
+    }
+
+    #[cfg(feature = "signing")]
+    fn signature(&self, key: &str, data: &[u8]) -> Option<String> {
+        self.signing_key
+            .as_ref()
+            .map(|secret| secret.sign(key, data))
+    }
+
+    #[cfg(not(feature = "signing"))]
+    #[allow(clippy::unused_self)]
+    const fn signature(&self, _key: &str, _data: &[u8]) -> Option<String> {
+        None
+    }
+
+    /// Signs each saved file with an HMAC-SHA256 keyed by `secret`, and refuses to load files
+    /// whose signature doesn't match.
+    ///
+    /// Files stay plain JSON that anyone can read; the signature goes in their metadata. A file
+    /// that was edited, or copied from another key, fails to load with
+    /// [`PreferencesError::InvalidSignature`](enum.PreferencesError.html#variant.InvalidSignature).
+    /// So do files saved before signing was enabled. This detects tampering by anyone who doesn't
+    /// know `secret`, which rules out secrets embedded in a program that users can inspect.
+    ///
+    /// (Requires the `signing` feature.)
+    ///
+    /// ```
+    /// use preferences::{AppInfo, FileBackend, Preferences};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let backend = FileBackend::new().with_signing_key("server-provided secret");
+    /// 1_000u64.save_in(&backend, &APP_INFO, "tests/docs/signed").unwrap();
+    /// assert_eq!(u64::load_in(&backend, &APP_INFO, "tests/docs/signed").unwrap(), 1_000);
+    /// ```
+    #[cfg(feature = "signing")]
+    #[must_use]
+    pub fn with_signing_key<K: AsRef<[u8]>>(mut self, secret: K) -> Self {
+        self.signing_key = Some(SigningKey::new(secret.as_ref()));
+        self
+    }
+
+    /// Records every save and deletion in an append-only log, to help track down where
+    /// unexpected changes came from. Use [`recent_events`](#method.recent_events) to read it.
+    ///
+    /// The log is kept per app, next to the app's directory. It only grows, so delete it once in
+    /// a while if it's enabled for good.
+    #[must_use]
+    pub const fn with_event_log(mut self) -> Self {
+        self.event_log = true;
+        self
+    }
+
+    /// Returns the last `limit` events recorded in the event log of `app`, oldest first.
+    ///
+    /// ```
+    /// use preferences::{AppInfo, ChangeKind, FileBackend, Preferences};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let backend = FileBackend::new().with_event_log();
+    /// 42.save_in(&backend, &APP_INFO, "tests/docs/events").unwrap();
+    /// let events = backend.recent_events(&APP_INFO, 100).unwrap();
+    /// let last = events.iter().rev().find(|e| e.key == "tests/docs/events").unwrap();
+    /// assert_eq!(last.kind, ChangeKind::Save);
+    /// assert_eq!(last.size, 2);
+    /// ```
+    ///
+    /// # Errors
+    /// If the log exists but can't be read.
+    pub fn recent_events(
+        &self,
+        app: &AppInfo,
+        limit: usize,
+    ) -> Result<Vec<ChangeEvent>, PreferencesError> {
+        events::read_recent(&self.app_file(app, EVENT_LOG_FILE_EXTENSION)?, limit)
+    }
+
+    /// Writes several keys as a single all-or-nothing operation.
+    ///
+    /// The new data, along with the data it replaces, is first recorded in a journal file, next
+    /// to the app's directory. Once the journal is safely on disk, each key is written in turn,
+    /// then the journal is deleted. If the process dies in between, the next call to
+    /// [`recover`](#method.recover) (or to this method) finishes the job, so after a crash either
+    /// every key is updated or none is. If writing a key fails, the keys written before it are
+    /// restored to their previous data.
+    ///
+    /// ```
+    /// use preferences::{AppInfo, FileBackend, Preferences};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let backend = FileBackend::new();
+    /// backend.recover(&APP_INFO).unwrap(); // On startup
+    /// let profile = String::from("alice").serialized().unwrap();
+    /// let volume = 11.serialized().unwrap();
+    /// backend
+    ///     .write_journaled(&APP_INFO, &[
+    ///         ("tests/docs/journal/profile", &profile),
+    ///         ("tests/docs/journal/volume", &volume),
+    ///     ])
+    ///     .unwrap();
+    /// assert_eq!(i32::load(&APP_INFO, "tests/docs/journal/volume").unwrap(), 11);
+    /// ```
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs. Keys already written are then rolled
+    /// back, as far as possible.
+    pub fn write_journaled(
+        &self,
+        app: &AppInfo,
+        writes: &[(&str, &[u8])],
+    ) -> Result<(), PreferencesError> {
+        let changes = writes
+            .iter()
+            .map(|&(key, data)| (key.into(), Some(data.into())));
+        self.commit_journal(app, changes.collect())
+    }
+
+    /// Writes (or, for changes without data, removes) several keys through the journal.
+    pub(crate) fn commit_journal(
+        &self,
+        app: &AppInfo,
+        changes: Vec<(String, Option<Vec<u8>>)>,
+    ) -> Result<(), PreferencesError> {
+        let journal = self.journal_path(app)?;
+        journal.parent().map(create_dir_all);
+        let _lock = lock(&journal, app.name, LockMode::Exclusive, self.lock_timeout)?;
+        self.replay(app, &journal)?;
+        let entries = changes
+            .into_iter()
+            .map(|(key, data)| {
+                let previous = match fs::read(self.path(app, &key)?) {
+                    Ok(previous) => Some(previous),
+                    Err(e) if e.kind() == ErrorKind::NotFound => None,
+                    Err(e) => return Err(e.into()),
+                };
+                Ok(JournalEntry {
+                    key,
+                    data,
+                    previous,
+                })
+            })
+            .collect::<Result<Vec<_>, PreferencesError>>()?;
+        // The journal is what makes the write all-or-nothing, so it always reaches the disk.
+        write_atomic(&journal, &serde_json::to_vec(&entries)?, 0, true)?;
+        self.apply(app, &journal, &entries)
+    }
+
+    /// Finishes a [`write_journaled`](#method.write_journaled) interrupted by a crash, if any.
+    /// Call this on startup, before loading data written that way.
+    ///
+    /// Returns `true` if an interrupted write was found (and finished).
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs, or if the journal can't be parsed.
+    /// Either way, the journal is then set aside as a `.journal.failed` file, so that it isn't
+    /// replayed again, and the keys it already wrote are rolled back as far as possible.
+    pub fn recover(&self, app: &AppInfo) -> Result<bool, PreferencesError> {
+        let journal = self.journal_path(app)?;
+        let _lock = lock(&journal, app.name, LockMode::Exclusive, self.lock_timeout)?;
+        self.replay(app, &journal)
+    }
+
+    fn journal_path(&self, app: &AppInfo) -> Result<PathBuf, PreferencesError> {
+        self.app_file(app, JOURNAL_FILE_EXTENSION)
+    }
+
+    /// Returns the path of a file next to the app's directory, named after the app.
+    pub(crate) fn app_file(
+        &self,
+        app: &AppInfo,
+        extension: &str,
+    ) -> Result<PathBuf, PreferencesError> {
+        // The empty key is stored there too.
+        let path = self.path(app, "")?;
+        Ok(path.with_file_name(format!("{}{}", sanitized(app.name), extension)))
+    }
+
+    fn replay(&self, app: &AppInfo, journal: &Path) -> Result<bool, PreferencesError> {
+        let file = match File::open(journal) {
+            Ok(file) => file,
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(false),
+            Err(e) => return Err(e.into()),
+        };
+        let entries: Vec<JournalEntry> = match serde_json::from_reader(io::BufReader::new(file)) {
+            Ok(entries) => entries,
+            Err(e) => {
+                quarantine(journal)?;
+                return Err(e.into());
+            }
+        };
+        self.apply(app, journal, &entries)?;
+        Ok(true)
+    }
+
+    /// Applies the entries of `journal`, then deletes it. If an entry can't be applied, the
+    /// entries before it are rolled back and the journal is set aside.
+    fn apply(
+        &self,
+        app: &AppInfo,
+        journal: &Path,
+        entries: &[JournalEntry],
+    ) -> Result<(), PreferencesError> {
+        for (applied, entry) in entries.iter().enumerate() {
+            if let Err(e) = self.apply_entry(app, &entry.key, entry.data.as_deref()) {
+                // The failed entry may have been partly applied too (e.g. a removal whose
+                // metadata couldn't be written), so it's rolled back with the others.
+                for entry in entries[..=applied].iter().rev() {
+                    let _ = self.apply_entry(app, &entry.key, entry.previous.as_deref());
+                }
+                quarantine(journal)?;
+                return Err(e);
+            }
+        }
+        Ok(fs::remove_file(journal)?)
+    }
+
+    fn apply_entry(
+        &self,
+        app: &AppInfo,
+        key: &str,
+        data: Option<&[u8]>,
+    ) -> Result<(), PreferencesError> {
+        data.map_or_else(
+            || self.remove(app, key),
+            |data| self.write_tagged(app, key, data, None),
+        )
+    }
+
+    fn lock(
+        &self,
+        path: &Path,
+        key: &str,
+        mode: LockMode,
+    ) -> Result<Option<File>, PreferencesError> {
+        self.lock_timeout
+            .map(|timeout| lock(path, key, mode, Some(timeout)))
+            .transpose()
+    }
+
+    /// Guards every read and write with an advisory lock, so that several processes using the
+    /// same files don't interleave their reads and writes.
+    ///
+    /// Reads share the lock with each other, while writes need it exclusively. The lock is taken
+    /// on a separate `.lock` file next to the data file. If the lock is still held by another
+    /// process after `timeout`, the operation fails with
+    /// [`PreferencesError::WouldBlock`](enum.PreferencesError.html#variant.WouldBlock).
+    /// Locks are advisory: they only protect against processes that also use them.
+    #[must_use]
+    pub const fn with_lock_timeout(mut self, timeout: Duration) -> Self {
+        self.lock_timeout = Some(timeout);
+        self
+    }
+
+    /// Uses `options` for every save.
+    #[must_use]
+    pub const fn with_save_options(mut self, options: SaveOptions) -> Self {
+        self.save_options = options;
+        self
+    }
+
+    /// Keeps the previous `count` versions of each file whenever a new save succeeds.
+    ///
+    /// The most recent backup of `name.prefs.json` is `name.prefs.json.bak`, the one before it
+    /// `name.prefs.json.bak.2`, and so on. Older backups are deleted as new ones are made. Use
+    /// [`history`](#method.history) and [`load_version`](#method.load_version) to look at them,
+    /// and [`restore_backup`](#method.restore_backup) to roll back.
+    #[must_use]
+    pub const fn with_backups(mut self, count: usize) -> Self {
+        self.backups = count;
+        self
+    }
+
+    /// Replaces the data saved under `key` with its most recent backup, moving every older
+    /// backup one step up. Each call therefore walks one version further back.
+    ///
+    /// The metadata the backup was saved with (e.g. its [`VersionHeader`](struct.VersionHeader.html))
+    /// is restored along with it, with a fresh signature if the backend signs its files. Backups
+    /// made before metadata was backed up are restored without metadata.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs. If there is no backup left, this is a
+    /// [`PreferencesError::Io`](enum.PreferencesError.html) with kind `NotFound`.
+    pub fn restore_backup(&self, app: &AppInfo, key: &str) -> Result<(), PreferencesError> {
+        let path = self.path(app, key)?;
+        let _lock = self.lock(&path, key, LockMode::Exclusive)?;
+        fs::rename(backup_path(&path, 1), &path)?;
+        let meta = metadata_path(&path);
+        let backed_up = read_metadata(&backup_path(&meta, 1)).unwrap_or_default();
+        remove_if_exists(&backup_path(&meta, 1))?;
+        for n in 2..=self.backups {
+            move_metadata_backup(&backup_path(&meta, n), &backup_path(&meta, n - 1))?;
+            match fs::rename(backup_path(&path, n), backup_path(&path, n - 1)) {
+                Err(e) if e.kind() == ErrorKind::NotFound => break,
+                result => result?,
+            }
+        }
+        let data = fs::read(&path)?;
+        let metadata = Metadata {
+            signature: self.signature(key, &data),
+            ..backed_up
+        };
+        write_metadata(&path, &metadata, self.save_options.sync)?;
+        self.log_event(app, key, Some(&data))
+    }
+
+    /// Records a checksum of each saved file in its metadata, and verifies it on load.
+    ///
+    /// A file whose contents no longer match its checksum fails to load with
+    /// [`PreferencesError::Corrupted`](enum.PreferencesError.html#variant.Corrupted), rather than
+    /// with whatever error the garbled data happens to cause. Files modified after their
+    /// metadata (e.g. edited by hand) are assumed to be changed on purpose, and aren't verified.
+    #[must_use]
+    pub const fn with_checksums(mut self) -> Self {
+        self.checksums = true;
+        self
+    }
+
+    /// Returns the previous versions of the data saved under `key`, most recent first.
+    ///
+    /// Only the versions kept by [`with_backups`](#method.with_backups) are available, so this is
+    /// empty unless backups are enabled.
+    ///
+    /// ```
+    /// use preferences::{AppInfo, FileBackend, Preferences};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let backend = FileBackend::new().with_backups(5);
+    /// let key = "tests/docs/history";
+    /// for volume in 1..=3 {
+    ///     volume.save_in(&backend, &APP_INFO, key).unwrap();
+    /// }
+    /// let history = backend.history(&APP_INFO, key).unwrap();
+    /// assert!(history.len() >= 2);
+    /// assert_eq!(backend.load_version::<i32>(&APP_INFO, key, history[0].number).unwrap(), 2);
+    /// ```
+    ///
+    /// # Errors
+    /// If the configuration directory can't be determined.
+    pub fn history(&self, app: &AppInfo, key: &str) -> Result<Vec<Revision>, PreferencesError> {
+        let path = self.path(app, key)?;
+        Ok((1..=self.backups)
+            .map_while(|number| {
+                let metadata = fs::metadata(backup_path(&path, number)).ok()?;
+                Some(Revision {
+                    number,
+                    saved_at: metadata.modified().ok(),
+                })
+            })
+            .collect())
+    }
+
+    /// Loads the version of the data saved under `key` from `number` saves back, as listed by
+    /// [`history`](#method.history). Version 0 is the current data.
+    ///
+    /// # Errors
+    /// If a deserialization or file I/O error (e.g. permission denied) occurs. If there is no such
+    /// version, this is a [`PreferencesError::Io`](enum.PreferencesError.html) with kind
+    /// `NotFound`.
+    pub fn load_version<T: Preferences>(
+        &self,
+        app: &AppInfo,
+        key: &str,
+        number: usize,
+    ) -> Result<T, PreferencesError> {
+        if number == 0 {
+            return T::load_in(self, app, key);
+        }
+        let path = self.path(app, key)?;
+        let _lock = self.lock(&path, key, LockMode::Shared)?;
+        let mut file = io::BufReader::new(File::open(backup_path(&path, number))?);
+        T::load_from(&mut file)
+    }
+
+    /// Deletes the data saved under `key`, along with its metadata. Deleting a key without data
+    /// isn't an error.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs.
+    pub fn remove(&self, app: &AppInfo, key: &str) -> Result<(), PreferencesError> {
+        let path = self.path(app, key)?;
+        let _lock = self.lock(&path, key, LockMode::Exclusive)?;
+        match fs::remove_file(&path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        write_metadata(&path, &Metadata::default(), self.save_options.sync)?;
+        self.log_event(app, key, None)
+    }
+
+    /// Falls back to `defaults` when loading a key that has no saved data.
+    ///
+    /// Deleting a key (with [`remove`](#method.remove) or
+    /// [`reset_to_default`](#method.reset_to_default)) makes it fall back to its default again.
+    #[must_use]
+    pub fn with_defaults(mut self, defaults: BundledDefaults) -> Self {
+        self.defaults = Some(Arc::new(defaults));
+        self
+    }
+
+    /// Deletes the data saved under `key`, so that it loads as its bundled default again.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs. If no default is registered for `key`
+    /// (see [`with_defaults`](#method.with_defaults)), this is a
+    /// [`PreferencesError::Io`](enum.PreferencesError.html) with kind `NotFound`, and the saved
+    /// data is left alone.
+    pub fn reset_to_default(&self, app: &AppInfo, key: &str) -> Result<(), PreferencesError> {
+        if self.default_data(key).is_none() {
+            let msg = format!("{key}: no bundled default");
+            return Err(io::Error::new(ErrorKind::NotFound, msg).into());
+        }
+        self.remove(app, key)
+    }
+
+    fn default_data(&self, key: &str) -> Option<&'static str> {
+        self.defaults
+            .as_ref()
+            .and_then(|defaults| defaults.get(key))
+    }
+
+    /// Moves the data saved under `key` out of the way, e.g. because it can't be loaded anymore,
+    /// and returns where it was moved to.
+    ///
+    /// The file is renamed by appending `.corrupt` to its name, replacing any file previously
+    /// set aside for the same key. Its metadata is removed, except for its
+    /// [`VersionHeader`](struct.VersionHeader.html), so that data saved in its place (e.g. a
+    /// fallback value) keeps counting as migrated.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs, or if no data exists for that `key`.
+    pub fn set_aside(&self, app: &AppInfo, key: &str) -> Result<PathBuf, PreferencesError> {
+        let path = self.path(app, key)?;
+        let _lock = self.lock(&path, key, LockMode::Exclusive)?;
+        let mut aside = path.as_os_str().to_owned();
+        aside.push(CORRUPT_FILE_EXTENSION);
+        let aside = PathBuf::from(aside);
+        let metadata = Metadata {
+            version: recorded_version(&path)?,
+            ..Metadata::default()
+        };
+        fs::rename(&path, &aside)?;
+        write_metadata(&path, &metadata, self.save_options.sync)?;
+        self.log_event(app, key, None)?;
+        Ok(aside)
+    }
+
+    /// Copies every file saved for `app` (except the data saved under the empty key) into a
+    /// snapshot named `label`, replacing any previous snapshot with the same label. Use
+    /// [`restore_snapshot`](#method.restore_snapshot) to go back to it.
+    ///
+    /// Snapshots are kept next to the app's directory until removed with
+    /// [`remove_snapshot`](#method.remove_snapshot).
+    ///
+    /// ```
+    /// use preferences::{AppInfo, FileBackend, Preferences};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let backend = FileBackend::new();
+    /// let key = "tests/docs/snapshot";
+    /// String::from("classic").save_in(&backend, &APP_INFO, key).unwrap();
+    /// backend.snapshot(&APP_INFO, "before experiment").unwrap();
+    /// String::from("experimental").save_in(&backend, &APP_INFO, key).unwrap();
+    /// backend.restore_snapshot(&APP_INFO, "before experiment").unwrap();
+    /// assert_eq!(String::load_in(&backend, &APP_INFO, key).unwrap(), "classic");
+    /// ```
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs, or if `label` is empty.
+    pub fn snapshot(&self, app: &AppInfo, label: &str) -> Result<(), PreferencesError> {
+        let snapshot = self.snapshot_path(app, label)?;
+        remove_dir(&snapshot)?;
+        copy_tree(&self.app_file(app, "")?, &snapshot)?;
+        Ok(())
+    }
+
+    /// Replaces every file saved for `app` (except the data saved under the empty key) with the
+    /// contents of the snapshot named `label`. The snapshot itself is kept.
+    ///
+    /// The current files are only removed once the snapshot has been copied, so a failed restore
+    /// leaves them in place.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs. If there is no such snapshot, this is
+    /// a [`PreferencesError::Io`](enum.PreferencesError.html) with kind `NotFound`. An empty `label`
+    /// is an error with kind `InvalidInput`.
+    pub fn restore_snapshot(&self, app: &AppInfo, label: &str) -> Result<(), PreferencesError> {
+        let snapshot = self.snapshot_path(app, label)?;
+        fs::metadata(&snapshot)?;
+        let dir = self.app_file(app, "")?;
+        let staging = self.app_file(
+            app,
+            &format!("{SNAPSHOTS_DIR_EXTENSION}{SHADOW_FILE_EXTENSION}"),
+        )?;
+        remove_dir(&staging)?;
+        copy_tree(&snapshot, &staging)?;
+        let old = self.app_file(app, &format!(".old{SHADOW_FILE_EXTENSION}"))?;
+        remove_dir(&old)?;
+        match fs::rename(&dir, &old) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e.into()),
+            _ => {}
+        }
+        fs::rename(&staging, &dir)?;
+        remove_dir(&old)?;
+        Ok(())
+    }
+
+    /// Returns the labels of the snapshots taken for `app`, sorted.
+    ///
+    /// Labels are sanitized like keys, so they may differ from the ones passed to
+    /// [`snapshot`](#method.snapshot) if those contained unusual characters.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs.
+    pub fn snapshots(&self, app: &AppInfo) -> Result<Vec<String>, PreferencesError> {
+        let entries = match fs::read_dir(self.app_file(app, SNAPSHOTS_DIR_EXTENSION)?) {
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            result => result?,
+        };
+        let mut labels = Vec::new();
+        for entry in entries {
+            labels.push(entry?.file_name().to_string_lossy().into_owned());
+        }
+        labels.sort();
+        Ok(labels)
+    }
+
+    /// Deletes the snapshot named `label`. Deleting a snapshot that doesn't exist isn't an error.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs, or if `label` is empty.
+    pub fn remove_snapshot(&self, app: &AppInfo, label: &str) -> Result<(), PreferencesError> {
+        Ok(remove_dir(&self.snapshot_path(app, label)?)?)
+    }
+
+    fn snapshot_path(&self, app: &AppInfo, label: &str) -> Result<PathBuf, PreferencesError> {
+        let dir = self.app_file(app, SNAPSHOTS_DIR_EXTENSION)?;
+        Ok(named_entry(&dir, label, "snapshot label")?)
+    }
+
+    /// Returns the names of the [`Profile`](struct.Profile.html)s that have data for `app`,
+    /// sorted.
+    ///
+    /// Names are sanitized like keys, so they may differ from the ones passed to
+    /// [`Profile::new`](struct.Profile.html#method.new) if those contained unusual characters.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs.
+    pub fn profiles(&self, app: &AppInfo) -> Result<Vec<String>, PreferencesError> {
+        let entries = match fs::read_dir(self.app_file(app, PROFILES_DIR_EXTENSION)?) {
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            result => result?,
+        };
+        let mut names = Vec::new();
+        for entry in entries {
+            let entry = entry?;
+            let name = entry.file_name().to_string_lossy().into_owned();
+            // Skip copies left over by an interrupted `copy_profile`.
+            if entry.file_type()?.is_dir() && !name.ends_with(SHADOW_FILE_EXTENSION) {
+                names.push(name);
+            }
+        }
+        names.sort();
+        Ok(names)
+    }
+
+    /// Copies all the data of profile `from` into a new profile `to`.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs. If profile `from` has no data, this is
+    /// a [`PreferencesError::Io`](enum.PreferencesError.html) with kind `NotFound`, and if profile
+    /// `to` already exists, one with kind `AlreadyExists`. An empty profile name is an error with
+    /// kind `InvalidInput`.
+    pub fn copy_profile(
+        &self,
+        app: &AppInfo,
+        from: &str,
+        to: &str,
+    ) -> Result<(), PreferencesError> {
+        let (from, to) = (self.profile_path(app, from)?, self.profile_path(app, to)?);
+        fs::metadata(&from)?;
+        check_vacant(&to)?;
+        let staging = shadow_dir(&to);
+        remove_dir(&staging)?;
+        copy_tree(&from, &staging)?;
+        fs::rename(&staging, &to)?;
+        Ok(())
+    }
+
+    /// Renames profile `from` to `to`.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs. If profile `from` has no data, this is
+    /// a [`PreferencesError::Io`](enum.PreferencesError.html) with kind `NotFound`, and if profile
+    /// `to` already exists, one with kind `AlreadyExists`. An empty profile name is an error with
+    /// kind `InvalidInput`.
+    pub fn rename_profile(
+        &self,
+        app: &AppInfo,
+        from: &str,
+        to: &str,
+    ) -> Result<(), PreferencesError> {
+        let (from, to) = (self.profile_path(app, from)?, self.profile_path(app, to)?);
+        fs::metadata(&from)?;
+        check_vacant(&to)?;
+        fs::rename(&from, &to)?;
+        Ok(())
+    }
+
+    /// Deletes all the data of profile `name`. Deleting a profile without data isn't an error.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs, or if `name` is empty.
+    pub fn remove_profile(&self, app: &AppInfo, name: &str) -> Result<(), PreferencesError> {
+        Ok(remove_dir(&self.profile_path(app, name)?)?)
+    }
+
+    /// Returns a copy of this backend storing its files in the directory of profile `name`.
+    pub(crate) fn in_profile(&self, app: &AppInfo, name: &str) -> Result<Self, PreferencesError> {
+        Ok(Self {
+            root: Root::Dir(self.profile_path(app, name)?),
+            ..self.clone()
+        })
+    }
+
+    fn profile_path(&self, app: &AppInfo, name: &str) -> Result<PathBuf, PreferencesError> {
+        let dir = self.app_file(app, PROFILES_DIR_EXTENSION)?;
+        Ok(named_entry(&dir, name, "profile name")?)
+    }
+
+    /// Reads the [`Metadata`](struct.Metadata.html) recorded for `key`.
+    ///
+    /// Files saved without any metadata have an empty `Metadata`.
+    ///
+    /// # Errors
+    /// If the metadata file exists but can't be read or parsed.
+    pub fn metadata(&self, app: &AppInfo, key: &str) -> Result<Metadata, PreferencesError> {
+        read_metadata(&metadata_path(&self.path(app, key)?))
+    }
+
+    /// Returns the path of the file that stores `key`.
+    ///
+    /// This makes no guarantees that the file (or its parent directory) actually exists.
+    ///
+    /// # Errors
+    /// If the configuration directory can't be determined.
+    pub fn path(&self, app: &AppInfo, key: &str) -> Result<PathBuf, PreferencesError> {
+        let mut path = match (&self.root, base_dir_override()) {
+            (Root::Platform(AppDataType::UserConfig), Some(dir)) => path_in_dir(&dir, app, key)?,
+            (&Root::Platform(data_type), _) => get_app_dir(data_type, app, key)?,
+            (Root::Dir(dir), _) => path_in_dir(dir, app, key)?,
+        };
+        let new_name = match path.file_name() {
+            Some(name) if !name.is_empty() => {
+                let mut new_name = OsString::with_capacity(name.len() + PREFS_FILE_EXTENSION.len());
+                new_name.push(name);
+                new_name.push(PREFS_FILE_EXTENSION);
+                new_name
+            }
+            _ => DEFAULT_PREFS_FILENAME.into(),
+        };
+        path.set_file_name(new_name);
+        Ok(path)
+    }
+}
+
+impl Default for Consistency {
+    fn default() -> Self {
+        Self::Unspecified
+    }
+}
+
+impl Default for FileBackend {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Backend for FileBackend {
+    fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        self.read_at(&self.path(app, key)?, key)
+    }
+    fn write(&self, app: &AppInfo, key: &str, data: &[u8]) -> Result<(), PreferencesError> {
+        self.write_tagged(app, key, data, None)
+    }
+    fn capabilities(&self) -> Capabilities {
+        Capabilities {
+            atomic: true,
+            transactional: true,
+            consistency: if self.save_options.sync {
+                Consistency::Durable
+            } else {
+                Consistency::Atomic
+            },
+            ..Capabilities::default()
+        }
+    }
+}
+
+/// Key, new data and previous data (`None` where the key has no data) of one write recorded in
+/// a journal.
+#[derive(Serialize, Deserialize)]
+struct JournalEntry {
+    key: String,
+    data: Option<Vec<u8>>,
+    previous: Option<Vec<u8>>,
+}
+
+/// Sets aside a journal that can't be applied, so that it isn't replayed on every commit.
+fn quarantine(journal: &Path) -> io::Result<()> {
+    let mut failed = journal.as_os_str().to_owned();
+    failed.push(".failed");
+    fs::rename(journal, failed)
+}
+
+/// Replaces the contents of `path` without ever exposing a partially written file, keeping up
+/// to `backups` previous versions.
+///
+/// The data is written to a shadow copy next to `path`, then renamed over `path`. Readers keep
+/// seeing the old file until the rename, and if anything fails before then, the old file is
+/// left untouched. If `sync` is set, the shadow copy and the rename are also flushed to disk.
+pub fn write_atomic(path: &Path, data: &[u8], backups: usize, sync: bool) -> io::Result<()> {
+    static NEXT_SHADOW: AtomicUsize = AtomicUsize::new(0);
+    let mut shadow = path.as_os_str().to_owned();
+    shadow.push(format!(
+        ".{}-{}{}",
+        process::id(),
+        NEXT_SHADOW.fetch_add(1, Ordering::Relaxed),
+        SHADOW_FILE_EXTENSION
+    ));
+    let shadow = PathBuf::from(shadow);
+    let result = File::create(&shadow)
+        .and_then(|mut file| {
+            file.write_all(data)?;
+            if sync {
+                file.sync_all()?;
+            }
+            Ok(())
+        })
+        .and_then(|()| rotate_backups(path, backups))
+        .and_then(|()| fs::rename(&shadow, path));
+    if result.is_err() {
+        let _ = fs::remove_file(&shadow);
+    }
+    result?;
+    if sync {
+        sync_parent(path)?;
+    }
+    Ok(())
+}
+
+fn backup_path(path: &Path, n: usize) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(BACKUP_FILE_EXTENSION);
+    if n > 1 {
+        name.push(format!(".{n}"));
+    }
+    name.into()
+}
+
+/// Shifts the backups of the metadata of `path` one step back, then backs up its current
+/// metadata, so that each backup of `path` made by `rotate_backups` keeps the metadata it was
+/// saved with.
+fn rotate_metadata_backups(path: &Path, backups: usize) -> io::Result<()> {
+    if backups == 0 || !path.exists() {
+        return Ok(());
+    }
+    let meta = metadata_path(path);
+    for n in (1..backups).rev() {
+        move_metadata_backup(&backup_path(&meta, n), &backup_path(&meta, n + 1))?;
+    }
+    match fs::copy(&meta, backup_path(&meta, 1)) {
+        Err(e) if e.kind() == ErrorKind::NotFound => remove_if_exists(&backup_path(&meta, 1)),
+        result => result.map(drop),
+    }
+}
+
+/// Moves a metadata backup from one slot to another. If there is none to move, the slot is
+/// emptied, since its data backup (moved alongside) had no metadata either.
+fn move_metadata_backup(from: &Path, to: &Path) -> io::Result<()> {
+    match fs::rename(from, to) {
+        Err(e) if e.kind() == ErrorKind::NotFound => remove_if_exists(to),
+        result => result,
+    }
+}
+
+/// Deletes the file `path`, if it exists.
+fn remove_if_exists(path: &Path) -> io::Result<()> {
+    match fs::remove_file(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Shifts the existing backups of `path` one step back, then backs up `path` itself.
+///
+/// The current file is hard-linked rather than moved, so that it stays in place until the new
+/// version is renamed over it.
+fn rotate_backups(path: &Path, backups: usize) -> io::Result<()> {
+    if backups == 0 || !path.exists() {
+        return Ok(());
+    }
+    for n in (1..backups).rev() {
+        match fs::rename(backup_path(path, n), backup_path(path, n + 1)) {
+            Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+            _ => {}
+        }
+    }
+    let backup = backup_path(path, 1);
+    match fs::remove_file(&backup) {
+        Err(e) if e.kind() != ErrorKind::NotFound => return Err(e),
+        _ => {}
+    }
+    fs::hard_link(path, &backup).or_else(|_| fs::copy(path, &backup).map(drop))
+}
+
+/// Makes sure a rename inside the parent directory of `path` has reached the disk.
+#[cfg(unix)]
+fn sync_parent(path: &Path) -> io::Result<()> {
+    match path.parent() {
+        Some(parent) => File::open(parent)?.sync_all(),
+        None => Ok(()),
+    }
+}
+
+// Other platforms can't open directories as files; NTFS journals renames anyway.
+#[cfg(not(unix))]
+#[allow(clippy::unnecessary_wraps)]
+fn sync_parent(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// Recursively copies the directory `from` to `to`, skipping lock files and shadow copies. A
+/// missing `from` is copied as an empty directory.
+fn copy_tree(from: &Path, to: &Path) -> io::Result<()> {
+    create_dir_all(to)?;
+    let entries = match fs::read_dir(from) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(()),
+        result => result?,
+    };
+    for entry in entries {
+        let entry = entry?;
+        let (source, target) = (entry.path(), to.join(entry.file_name()));
+        if entry.file_type()?.is_dir() {
+            copy_tree(&source, &target)?;
+        } else if !matches!(source.extension(), Some(ext) if ext == "lock" || ext == "tmp") {
+            fs::copy(&source, &target)?;
+        }
+    }
+    Ok(())
+}
+
+/// Returns the entry of `dir` named `name`, sanitized. Fails with an `InvalidInput` error if
+/// `name` is empty, since that would name `dir` itself.
+fn named_entry(dir: &Path, name: &str, what: &str) -> io::Result<PathBuf> {
+    if name.is_empty() {
+        let msg = format!("{what} can't be empty");
+        return Err(io::Error::new(ErrorKind::InvalidInput, msg));
+    }
+    Ok(dir.join(sanitized(name)))
+}
+
+/// Fails with an `AlreadyExists` error if something exists at `path`.
+fn check_vacant(path: &Path) -> io::Result<()> {
+    if path.exists() {
+        let msg = format!("{} already exists", path.display());
+        return Err(io::Error::new(ErrorKind::AlreadyExists, msg));
+    }
+    Ok(())
+}
+
+/// Returns the path of a temporary directory next to `path`, to stage its contents in.
+fn shadow_dir(path: &Path) -> PathBuf {
+    let mut shadow = path.as_os_str().to_owned();
+    shadow.push(SHADOW_FILE_EXTENSION);
+    shadow.into()
+}
+
+/// Deletes the directory `path` and everything in it, if it exists.
+fn remove_dir(path: &Path) -> io::Result<()> {
+    match fs::remove_dir_all(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(()),
+        result => result,
+    }
+}
+
+/// Returns the directory named by [`BASE_DIR_ENV_VAR`](static.BASE_DIR_ENV_VAR.html), if set.
+pub fn base_dir_override() -> Option<PathBuf> {
+    env::var_os(BASE_DIR_ENV_VAR)
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+}
+
+/// Returns the path of `key` for `app` under base directory `dir`, without an extension.
+fn path_in_dir(dir: &Path, app: &AppInfo, key: &str) -> Result<PathBuf, PreferencesError> {
+    if app.author.is_empty() || app.name.is_empty() {
+        return Err(AppDirsError::InvalidAppInfo.into());
+    }
+    let mut path = dir.join(sanitized(app.name));
+    path.extend(key.split('/').filter(|s| !s.is_empty()).map(sanitized));
+    Ok(path)
+}
+
+fn metadata_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(METADATA_FILE_EXTENSION);
+    name.into()
+}
+
+/// Checks `data` against the checksum recorded in the metadata of `path`, if any.
+fn verify_checksum(path: &Path, key: &str, data: &[u8]) -> Result<(), PreferencesError> {
+    let metadata_path = metadata_path(path);
+    match read_metadata(&metadata_path)?.checksum {
+        Some(checksum) if checksum != crc32(data) && !modified_after(path, &metadata_path) => {
+            Err(PreferencesError::Corrupted(key.into()))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks that the version header recorded in the metadata of `path`, if any, isn't newer than
+/// `schema`.
+fn check_version(path: &Path, key: &str, schema: u32) -> Result<(), PreferencesError> {
+    match read_metadata(&metadata_path(path))?.version {
+        Some(version) if version.is_newer_than(schema) => {
+            Err(PreferencesError::UnsupportedVersion(key.into(), version))
+        }
+        _ => Ok(()),
+    }
+}
+
+/// Checks `data` against the signature recorded in the metadata of `path`, which must exist.
+#[cfg(feature = "signing")]
+fn verify_signature(
+    path: &Path,
+    key: &str,
+    data: &[u8],
+    secret: &SigningKey,
+) -> Result<(), PreferencesError> {
+    match read_metadata(&metadata_path(path))?.signature {
+        Some(ref signature) if secret.verify(key, data, signature) => Ok(()),
+        _ => Err(PreferencesError::InvalidSignature(key.into())),
+    }
+}
+
+fn modified_after(path: &Path, other: &Path) -> bool {
+    let modified = |path: &Path| fs::metadata(path).and_then(|m| m.modified()).ok();
+    match (modified(path), modified(other)) {
+        (Some(modified), Some(other)) => modified > other,
+        _ => false,
+    }
+}
+
+fn read_metadata(path: &Path) -> Result<Metadata, PreferencesError> {
+    match File::open(path) {
+        Ok(file) => Ok(serde_json::from_reader(file)?),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(Metadata::default()),
+        Err(e) => Err(e.into()),
+    }
+}
+
+/// Returns the version header recorded for `path`, if any. A sidecar that can't be parsed counts
+/// as recording none, so that saving over it repairs it rather than failing.
+fn recorded_version(path: &Path) -> io::Result<Option<VersionHeader>> {
+    match fs::read(metadata_path(path)) {
+        Ok(data) => Ok(serde_json::from_slice::<Metadata>(&data)
+            .ok()
+            .and_then(|metadata| metadata.version)),
+        Err(e) if e.kind() == ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+fn write_metadata(path: &Path, metadata: &Metadata, sync: bool) -> Result<(), PreferencesError> {
+    let path = metadata_path(path);
+    if metadata.is_empty() {
+        match fs::remove_file(path) {
+            Err(e) if e.kind() != ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    } else {
+        Ok(write_atomic(
+            &path,
+            &serde_json::to_vec(metadata)?,
+            0,
+            sync,
+        )?)
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for &B {
+    fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        (**self).read(app, key)
+    }
+    fn write(&self, app: &AppInfo, key: &str, data: &[u8]) -> Result<(), PreferencesError> {
+        (**self).write(app, key, data)
+    }
+    fn capabilities(&self) -> Capabilities {
+        (**self).capabilities()
+    }
+}
+
+impl<B: Backend + ?Sized> Backend for Box<B> {
+    fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        (**self).read(app, key)
+    }
+    fn write(&self, app: &AppInfo, key: &str, data: &[u8]) -> Result<(), PreferencesError> {
+        (**self).write(app, key, data)
+    }
+    fn capabilities(&self) -> Capabilities {
+        (**self).capabilities()
+    }
+}
@@ -0,0 +1,53 @@
+use crate::{PreferencesError, PreferencesMap};
+
+/// Outcome of an operation on many keys at once, such as
+/// [`Preferences::load_many`](trait.Preferences.html#method.load_many).
+///
+/// Bulk operations don't stop at the first error: every key is attempted, and the report lists
+/// which keys succeeded and which failed (with the reason), so callers can use partial results
+/// and tell the user exactly which keys need attention.
+#[derive(Debug)]
+#[non_exhaustive]
+pub struct BulkReport<T> {
+    /// Results of the keys that succeeded.
+    pub succeeded: PreferencesMap<T>,
+    /// Keys that failed, with their errors, in the order they were attempted.
+    pub failed: Vec<(String, PreferencesError)>,
+}
+
+impl<T> BulkReport<T> {
+    /// Returns `true` if every key succeeded.
+    #[must_use]
+    pub fn is_ok(&self) -> bool {
+        self.failed.is_empty()
+    }
+
+    /// Returns the results of every key, or the first error if any key failed.
+    ///
+    /// # Errors
+    /// If any key failed.
+    pub fn into_result(self) -> Result<PreferencesMap<T>, PreferencesError> {
+        match self.failed.into_iter().next() {
+            Some((_, e)) => Err(e),
+            None => Ok(self.succeeded),
+        }
+    }
+
+    pub(crate) fn record(&mut self, key: &str, result: Result<T, PreferencesError>) {
+        match result {
+            Ok(value) => {
+                self.succeeded.insert(key.into(), value);
+            }
+            Err(e) => self.failed.push((key.into(), e)),
+        }
+    }
+}
+
+impl<T> Default for BulkReport<T> {
+    fn default() -> Self {
+        Self {
+            succeeded: PreferencesMap::new(),
+            failed: Vec::new(),
+        }
+    }
+}
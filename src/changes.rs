@@ -0,0 +1,145 @@
+use crate::metadata::crc32;
+use crate::{FileBackend, Preferences, PreferencesError};
+use app_dirs::AppInfo;
+use async_channel::{unbounded, Receiver, Sender};
+use futures_lite::Stream;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::Path;
+use std::pin::Pin;
+use std::task::{Context, Poll};
+use std::thread;
+use std::time::{Duration, SystemTime};
+
+const DEFAULT_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Asynchronous stream of changes to the user data saved under a key. (Requires the `async`
+/// feature.)
+///
+/// Every time the file behind the key changes on disk, whether through this process, another
+/// one, or a hand-edit, the stream yields the freshly loaded value, or the error that occurred
+/// while loading it (e.g. "not found" if the file was removed). Changes are detected by
+/// polling the file in a background thread, which stops when the stream is dropped. A change is
+/// only reported once the file has stayed the same for a full polling interval.
+///
+/// Each poll compares the file's modification time and size, and a checksum of its contents, so
+/// that a change is noticed even if it keeps the file's size and lands within the same tick of a
+/// coarse file system clock. Since the file is read on every poll, keep the interval reasonable
+/// for large files.
+///
+/// ```no_run
+/// use futures_lite::StreamExt;
+/// use preferences::{AppInfo, ChangeStream, PreferencesMap};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// # futures_lite::future::block_on(async {
+/// let mut changes = ChangeStream::<PreferencesMap>::new(&APP_INFO, "options/graphics").unwrap();
+/// while let Some(change) = changes.next().await {
+///     match change {
+///         Ok(prefs) => println!("Graphics options are now {:?}", prefs),
+///         Err(e) => println!("Couldn't reload graphics options: {}", e),
+///     }
+/// }
+/// # });
+/// ```
+#[derive(Debug)]
+pub struct ChangeStream<T> {
+    receiver: Pin<Box<Receiver<Result<T, PreferencesError>>>>,
+}
+
+impl<T: Preferences + Send + 'static> ChangeStream<T> {
+    /// Starts watching the user data saved under `key`.
+    ///
+    /// # Errors
+    /// If the location of the preferences file can't be determined.
+    pub fn new<S: AsRef<str>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError> {
+        Self::with_interval(app, key, DEFAULT_INTERVAL)
+    }
+
+    /// Same as `new`, but checks for changes every `interval` instead of twice a second.
+    ///
+    /// # Errors
+    /// If the location of the preferences file can't be determined.
+    pub fn with_interval<S: AsRef<str>>(
+        app: &AppInfo,
+        key: S,
+        interval: Duration,
+    ) -> Result<Self, PreferencesError> {
+        Self::with_backend(&FileBackend::new(), app, key, interval)
+    }
+
+    /// Same as `with_interval`, but watches the data saved in `backend`, loading it with the
+    /// backend's checks and defaults.
+    ///
+    /// # Errors
+    /// If the location of the preferences file can't be determined.
+    pub fn with_backend<S: AsRef<str>>(
+        backend: &FileBackend,
+        app: &AppInfo,
+        key: S,
+        interval: Duration,
+    ) -> Result<Self, PreferencesError> {
+        let path = backend.path(app, key.as_ref())?;
+        let (sender, receiver) = unbounded();
+        let reload = {
+            let backend = backend.clone();
+            let app = app.clone();
+            let key = key.as_ref().to_owned();
+            move || T::load_in(&backend, &app, &key)
+        };
+        thread::spawn(move || poll(&path, interval, reload, &sender));
+        Ok(Self {
+            receiver: Box::pin(receiver),
+        })
+    }
+}
+
+impl<T> Stream for ChangeStream<T> {
+    type Item = Result<T, PreferencesError>;
+
+    fn poll_next(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        self.receiver.as_mut().poll_next(cx)
+    }
+}
+
+/// What the file looked like when last checked, or `None` if it didn't exist.
+type Signature = Option<(SystemTime, u64, u32)>;
+
+fn signature(path: &Path) -> Result<Signature, PreferencesError> {
+    let metadata = match fs::metadata(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        result => result?,
+    };
+    let checksum = match fs::read(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+        result => crc32(&result?),
+    };
+    Ok(Some((metadata.modified()?, metadata.len(), checksum)))
+}
+
+fn poll<T, F>(
+    path: &Path,
+    interval: Duration,
+    reload: F,
+    sender: &Sender<Result<T, PreferencesError>>,
+) where
+    F: Fn() -> Result<T, PreferencesError>,
+{
+    let mut reported = signature(path).ok();
+    let mut previous = reported;
+    while !sender.is_closed() {
+        thread::sleep(interval);
+        let current = signature(path).ok();
+        // Only report a change once the file has stopped changing, so that a save in progress
+        // isn't read half-written.
+        let settled = current == previous;
+        previous = current;
+        if !settled || current == reported {
+            continue;
+        }
+        reported = current;
+        if sender.try_send(reload()).is_err() {
+            break;
+        }
+    }
+}
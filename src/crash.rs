@@ -0,0 +1,111 @@
+use crate::metadata::unix_time;
+use crate::{Preferences, PreferencesError};
+use app_dirs::AppInfo;
+use serde::{Deserialize, Serialize};
+
+static DEFAULT_CRASH_MARKER_KEY: &str = "crash-marker";
+
+#[derive(Clone, Copy, Debug, Default, Serialize, Deserialize)]
+struct Session {
+    armed: bool,
+    armed_at: u64,
+    disarmed_at: Option<u64>,
+}
+
+/// Flag recording whether the app shut down cleanly last time it ran.
+///
+/// Arm the marker on startup and disarm it on a clean exit. If the app crashes (or is killed)
+/// in between, the marker is still armed on the next startup, so the app can offer to start in
+/// safe mode or to reset its settings. The flag is saved as regular user data.
+///
+/// Dropping the marker doesn't disarm it, since that would also happen while unwinding from a
+/// panic.
+///
+/// ```
+/// use preferences::{AppInfo, CrashMarker};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let marker = CrashMarker::arm_key(&APP_INFO, "tests/docs/crash-marker").unwrap();
+/// if marker.was_unclean_shutdown() {
+///     // Offer to reset the settings...
+/// }
+/// // Run the app...
+/// marker.disarm().unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct CrashMarker {
+    app: AppInfo,
+    key: String,
+    previous: Option<Session>,
+    current: Session,
+}
+
+impl CrashMarker {
+    /// Arms the crash marker of `app`, remembering whether the previous run left it armed.
+    ///
+    /// # Errors
+    /// If the marker can't be saved, or if the saved marker exists but can't be read.
+    pub fn arm(app: &AppInfo) -> Result<Self, PreferencesError> {
+        Self::arm_key(app, DEFAULT_CRASH_MARKER_KEY)
+    }
+
+    /// Same as `arm`, but uses a custom preferences key to store the marker.
+    ///
+    /// # Errors
+    /// If the marker can't be saved, or if the saved marker exists but can't be read.
+    pub fn arm_key<S: AsRef<str>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError> {
+        let key = key.as_ref();
+        let previous = match Session::load(app, key) {
+            Err(e) if e.is_not_found() => None,
+            result => Some(result?),
+        };
+        let current = Session {
+            armed: true,
+            armed_at: unix_time(),
+            disarmed_at: None,
+        };
+        current.save(app, key)?;
+        Ok(Self {
+            app: app.clone(),
+            key: key.into(),
+            previous,
+            current,
+        })
+    }
+
+    /// Records a clean shutdown.
+    ///
+    /// # Errors
+    /// If the marker can't be saved.
+    pub fn disarm(mut self) -> Result<(), PreferencesError> {
+        self.current.armed = false;
+        self.current.disarmed_at = Some(unix_time());
+        self.current.save(&self.app, &self.key)
+    }
+
+    /// Returns `true` if the previous run armed the marker but never disarmed it.
+    #[must_use]
+    pub fn was_unclean_shutdown(&self) -> bool {
+        self.previous.map_or(false, |previous| previous.armed)
+    }
+
+    /// Returns when the previous run armed the marker, in seconds since the Unix epoch, or `None`
+    /// if this is the first run.
+    #[must_use]
+    pub fn previous_armed_at(&self) -> Option<u64> {
+        self.previous.map(|previous| previous.armed_at)
+    }
+
+    /// Returns when the previous run disarmed the marker, in seconds since the Unix epoch, or
+    /// `None` if it never did.
+    #[must_use]
+    pub fn previous_disarmed_at(&self) -> Option<u64> {
+        self.previous.and_then(|previous| previous.disarmed_at)
+    }
+
+    /// Returns when this run armed the marker, in seconds since the Unix epoch.
+    #[must_use]
+    pub const fn armed_at(&self) -> u64 {
+        self.current.armed_at
+    }
+}
@@ -0,0 +1,74 @@
+use crate::{Backend, PreferencesError};
+use app_dirs::AppInfo;
+use std::collections::HashMap;
+use std::io::{self, ErrorKind};
+
+/// Default data embedded in the program, e.g. with `include_str!`, for each key.
+///
+/// Attached to a [`FileBackend`](struct.FileBackend.html) with
+/// [`with_defaults`](struct.FileBackend.html#method.with_defaults), it provides the data of keys
+/// the user hasn't saved yet, and [`reset_to_default`](struct.FileBackend.html#method.reset_to_default)
+/// brings a key back to it. Unlike a defaults file shipped next to the binary, embedded defaults
+/// can't go missing or get out of sync with the program.
+///
+/// The data must be in the format the backend saves in, i.e. JSON. It's only parsed when loaded.
+/// On its own, this is also a read-only [`Backend`](trait.Backend.html) holding the defaults.
+///
+/// ```
+/// use preferences::{AppInfo, BundledDefaults, FileBackend, Preferences, PreferencesMap};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let key = "tests/docs/bundled";
+/// // Usually `include_str!("defaults/theme.json")`.
+/// let defaults = BundledDefaults::new().with(key, r#"{"theme": "light"}"#);
+/// let backend = FileBackend::new().with_defaults(defaults);
+/// backend.reset_to_default(&APP_INFO, key).unwrap();
+///
+/// let mut prefs = PreferencesMap::<String>::load_in(&backend, &APP_INFO, key).unwrap();
+/// assert_eq!(prefs["theme"], "light");
+/// prefs.insert("theme".into(), "dark".into());
+/// prefs.save_in(&backend, &APP_INFO, key).unwrap();
+///
+/// backend.reset_to_default(&APP_INFO, key).unwrap();
+/// let prefs = PreferencesMap::<String>::load_in(&backend, &APP_INFO, key).unwrap();
+/// assert_eq!(prefs["theme"], "light");
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct BundledDefaults {
+    entries: HashMap<String, &'static str>,
+}
+
+impl BundledDefaults {
+    /// Creates an empty set of defaults.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers `data` as the default for `key`, replacing any previous default for it.
+    #[must_use]
+    pub fn with<S: Into<String>>(mut self, key: S, data: &'static str) -> Self {
+        self.entries.insert(key.into(), data);
+        self
+    }
+
+    /// Returns the default registered for `key`, if any.
+    #[must_use]
+    pub fn get(&self, key: &str) -> Option<&'static str> {
+        self.entries.get(key).copied()
+    }
+}
+
+impl Backend for BundledDefaults {
+    fn read(&self, _app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        self.get(key)
+            .map(|data| data.as_bytes().to_vec())
+            .ok_or_else(|| {
+                let msg = format!("{key}: no bundled default");
+                io::Error::new(ErrorKind::NotFound, msg).into()
+            })
+    }
+    fn write(&self, _app: &AppInfo, key: &str, _data: &[u8]) -> Result<(), PreferencesError> {
+        Err(PreferencesError::ReadOnly(key.into()))
+    }
+}
@@ -0,0 +1,154 @@
+use crate::PreferencesError;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+
+/// Key-value store whose values can be any JSON value: booleans, numbers, strings, arrays, and
+/// nested documents.
+///
+/// Unlike a `PreferencesMap<String>`, values keep their type when saved, so they don't need to
+/// be formatted and parsed by hand. Nested values are reached with dotted paths: `"window.width"`
+/// is the `width` field of the `window` document.
+///
+/// ```
+/// use preferences::{AppInfo, Preferences, PrefsDocument};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let mut doc = PrefsDocument::new();
+/// doc.set("fullscreen", true).unwrap();
+/// doc.set("window.width", 800).unwrap();
+/// doc.set("recent", ["a.txt", "b.txt"]).unwrap();
+///
+/// let key = "tests/docs/document";
+/// doc.save(&APP_INFO, key).unwrap();
+/// let doc = PrefsDocument::load(&APP_INFO, key).unwrap();
+/// assert_eq!(doc.get_bool("fullscreen"), Some(true));
+/// assert_eq!(doc.get_i64("window.width"), Some(800));
+/// assert_eq!(doc.get::<Vec<String>>("recent").unwrap().unwrap().len(), 2);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct PrefsDocument {
+    fields: Map<String, Value>,
+}
+
+impl PrefsDocument {
+    /// Creates an empty document.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Returns the value at `path`, if it exists.
+    #[must_use]
+    pub fn get_value(&self, path: &str) -> Option<&Value> {
+        let mut names = path.split('.');
+        let first = self.fields.get(names.next()?)?;
+        names.try_fold(first, |value, name| value.get(name))
+    }
+
+    /// Deserializes the value at `path` as a `T`.
+    ///
+    /// # Errors
+    /// If the value exists but can't be deserialized as a `T`.
+    pub fn get<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, PreferencesError> {
+        self.get_value(path)
+            .map(|value| T::deserialize(value).map_err(PreferencesError::from))
+            .transpose()
+    }
+
+    /// Returns the value at `path` if it's a boolean.
+    #[must_use]
+    pub fn get_bool(&self, path: &str) -> Option<bool> {
+        self.get_value(path).and_then(Value::as_bool)
+    }
+
+    /// Returns the value at `path` if it's an integer that fits in an `i64`.
+    #[must_use]
+    pub fn get_i64(&self, path: &str) -> Option<i64> {
+        self.get_value(path).and_then(Value::as_i64)
+    }
+
+    /// Returns the value at `path` if it's a number.
+    #[must_use]
+    pub fn get_f64(&self, path: &str) -> Option<f64> {
+        self.get_value(path).and_then(Value::as_f64)
+    }
+
+    /// Returns the value at `path` if it's a string.
+    #[must_use]
+    pub fn get_str(&self, path: &str) -> Option<&str> {
+        self.get_value(path).and_then(Value::as_str)
+    }
+
+    /// Returns the value at `path` if it's an array.
+    #[must_use]
+    pub fn get_array(&self, path: &str) -> Option<&Vec<Value>> {
+        self.get_value(path).and_then(Value::as_array)
+    }
+
+    /// Sets the value at `path`, creating the documents leading to it as needed. Values in the
+    /// way that aren't documents are replaced.
+    ///
+    /// # Errors
+    /// If `value` can't be serialized. The document is left unchanged in that case.
+    pub fn set<V: Serialize>(&mut self, path: &str, value: V) -> Result<(), PreferencesError> {
+        let value = serde_json::to_value(value)?;
+        *self.slot(path) = value;
+        Ok(())
+    }
+
+    /// Removes the value at `path`, returning it if it existed.
+    pub fn remove(&mut self, path: &str) -> Option<Value> {
+        let (parent, last) = match path.rsplit_once('.') {
+            Some((parent, last)) => (self.get_value_mut(parent)?.as_object_mut()?, last),
+            None => (&mut self.fields, path),
+        };
+        parent.remove(last)
+    }
+
+    /// Returns the top-level fields of this document.
+    #[must_use]
+    pub const fn as_map(&self) -> &Map<String, Value> {
+        &self.fields
+    }
+
+    /// Unwraps this document, returning its top-level fields.
+    #[must_use]
+    pub fn into_map(self) -> Map<String, Value> {
+        self.fields
+    }
+
+    /// Returns the value at `path`, inserting `null` there (and creating the documents leading to
+    /// it, like `set`) if it doesn't exist.
+    pub(crate) fn slot(&mut self, path: &str) -> &mut Value {
+        let mut names = path.split('.');
+        let last = names.next_back().unwrap_or_default();
+        let mut fields = &mut self.fields;
+        for name in names {
+            let entry = fields
+                .entry(name)
+                .or_insert_with(|| Value::Object(Map::new()));
+            if !entry.is_object() {
+                *entry = Value::Object(Map::new());
+            }
+            fields = match *entry {
+                Value::Object(ref mut fields) => fields,
+                _ => unreachable!(),
+            };
+        }
+        fields.entry(last).or_insert(Value::Null)
+    }
+
+    pub(crate) fn get_value_mut(&mut self, path: &str) -> Option<&mut Value> {
+        let mut names = path.split('.');
+        let first = self.fields.get_mut(names.next()?)?;
+        names.try_fold(first, |value, name| value.get_mut(name))
+    }
+}
+
+impl From<Map<String, Value>> for PrefsDocument {
+    fn from(fields: Map<String, Value>) -> Self {
+        Self { fields }
+    }
+}
@@ -0,0 +1,127 @@
+use crate::{FileBackend, Preferences, PreferencesError, PrefsDocument};
+use app_dirs::AppInfo;
+use serde::Serialize;
+use std::fs::create_dir_all;
+
+/// Loads the value saved under `key` (or the default value, if there is none), passes it to `f`
+/// to modify, and saves the result, all while holding the key's exclusive lock. Returns what `f`
+/// returns.
+///
+/// Every process (or thread) editing the key this way waits for the others, so no edit is lost
+/// to a concurrent one, as could happen with separate calls to `load` and `save`. The lock is
+/// advisory, though: writers that don't take it (see
+/// [`FileBackend::with_lock_timeout`](struct.FileBackend.html#method.with_lock_timeout)) aren't
+/// held back.
+///
+/// ```
+/// use preferences::{edit, AppInfo, PreferencesMap};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let launches = edit(&APP_INFO, "tests/docs/edit", |prefs: &mut PreferencesMap<u32>| {
+///     let launches = prefs.entry("launches".into()).or_insert(0);
+///     *launches += 1;
+///     *launches
+/// });
+/// assert!(launches.unwrap() >= 1);
+/// ```
+///
+/// # Errors
+/// If the data exists but can't be read or deserialized, or if the lock can't be taken, or if a
+/// serialization or file I/O error occurs while saving.
+pub fn edit<T, R, S, F>(app: &AppInfo, key: S, f: F) -> Result<R, PreferencesError>
+where
+    T: Preferences + Default,
+    S: AsRef<str>,
+    F: FnOnce(&mut T) -> R,
+{
+    edit_in(&FileBackend::new(), app, key, f)
+}
+
+/// Same as `edit`, but uses `backend`, waiting up to its lock timeout (if any) for the lock.
+///
+/// # Errors
+/// If the data exists but can't be read or deserialized, or if the lock can't be taken in time,
+/// or if a serialization or file I/O error occurs while saving.
+pub fn edit_in<T, R, S, F>(
+    backend: &FileBackend,
+    app: &AppInfo,
+    key: S,
+    f: F,
+) -> Result<R, PreferencesError>
+where
+    T: Preferences + Default,
+    S: AsRef<str>,
+    F: FnOnce(&mut T) -> R,
+{
+    let key = key.as_ref();
+    let path = backend.path(app, key)?;
+    path.parent().map(create_dir_all);
+    let (backend, _lock) = backend.hold_lock(&path, key)?;
+    let mut value = match T::load_in(&backend, app, key) {
+        Err(e) if e.is_not_found() => T::default(),
+        result => result?,
+    };
+    let result = f(&mut value);
+    value.save_in(&backend, app, key)?;
+    Ok(result)
+}
+
+/// Sets one field of the JSON object saved under `key`, leaving the others as they are.
+///
+/// This doesn't need a type for the whole object, so it works on data whose shape the caller
+/// doesn't know. Like [`edit`](fn.edit.html), it holds the key's exclusive lock while it works.
+/// `field` is a dotted path into nested objects, as in
+/// [`PrefsDocument::set`](struct.PrefsDocument.html#method.set). Missing data is treated as an
+/// empty object.
+///
+/// ```
+/// use preferences::{update_field, AppInfo, Preferences};
+/// use serde_json::json;
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let key = "tests/docs/update-field";
+/// json!({"volume": 0.5, "plugin": {"enabled": false}}).save(&APP_INFO, key).unwrap();
+/// update_field(&APP_INFO, key, "volume", 0.8).unwrap();
+/// update_field(&APP_INFO, key, "plugin.enabled", true).unwrap();
+/// let saved = serde_json::Value::load(&APP_INFO, key).unwrap();
+/// assert_eq!(saved, json!({"volume": 0.8, "plugin": {"enabled": true}}));
+/// ```
+///
+/// # Errors
+/// If the saved data isn't a JSON object, if `value` can't be serialized, or if the lock can't be
+/// taken or a file I/O error occurs.
+pub fn update_field<S, V>(
+    app: &AppInfo,
+    key: S,
+    field: &str,
+    value: V,
+) -> Result<(), PreferencesError>
+where
+    S: AsRef<str>,
+    V: Serialize,
+{
+    update_field_in(&FileBackend::new(), app, key, field, value)
+}
+
+/// Same as `update_field`, but uses `backend`, waiting up to its lock timeout (if any) for the
+/// lock.
+///
+/// # Errors
+/// If the saved data isn't a JSON object, if `value` can't be serialized, or if the lock can't be
+/// taken in time or a file I/O error occurs.
+pub fn update_field_in<S, V>(
+    backend: &FileBackend,
+    app: &AppInfo,
+    key: S,
+    field: &str,
+    value: V,
+) -> Result<(), PreferencesError>
+where
+    S: AsRef<str>,
+    V: Serialize,
+{
+    let value = serde_json::to_value(value)?;
+    edit_in(backend, app, key, |document: &mut PrefsDocument| {
+        document.set(field, value)
+    })?
+}
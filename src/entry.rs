@@ -0,0 +1,67 @@
+use crate::{Backend, FileBackend, Preferences, PreferencesError, PreferencesMap};
+use app_dirs::AppInfo;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+
+/// Returns the entry `name` of the [`PreferencesMap`](type.PreferencesMap.html) saved under
+/// `key`.
+///
+/// If the entry (or the whole map) doesn't exist yet, inserts the value returned by `f`, saves
+/// the map, and returns that value.
+///
+/// ```
+/// use preferences::{get_or_insert_with, AppInfo};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let key = "tests/docs/entry";
+/// let volume: u32 = get_or_insert_with(&APP_INFO, key, "volume", || 11).unwrap();
+/// // The value is saved, so `f` isn't called again.
+/// assert_eq!(get_or_insert_with(&APP_INFO, key, "volume", || 0).unwrap(), volume);
+/// ```
+///
+/// # Errors
+/// If the map exists but can't be read or deserialized, or if it can't be saved.
+pub fn get_or_insert_with<T, S, F>(
+    app: &AppInfo,
+    key: S,
+    name: &str,
+    f: F,
+) -> Result<T, PreferencesError>
+where
+    T: Serialize + DeserializeOwned + Clone,
+    S: AsRef<str>,
+    F: FnOnce() -> T,
+{
+    get_or_insert_with_in(&FileBackend::new(), app, key, name, f)
+}
+
+/// Same as `get_or_insert_with`, but uses an arbitrary [`Backend`](trait.Backend.html).
+///
+/// # Errors
+/// If the map exists but can't be read or deserialized, or if the backend fails to save it.
+pub fn get_or_insert_with_in<B, T, S, F>(
+    backend: &B,
+    app: &AppInfo,
+    key: S,
+    name: &str,
+    f: F,
+) -> Result<T, PreferencesError>
+where
+    B: Backend + ?Sized,
+    T: Serialize + DeserializeOwned + Clone,
+    S: AsRef<str>,
+    F: FnOnce() -> T,
+{
+    let key = key.as_ref();
+    let mut map = match PreferencesMap::<T>::load_in(backend, app, key) {
+        Err(e) if e.is_not_found() => PreferencesMap::new(),
+        result => result?,
+    };
+    if let Some(value) = map.get(name) {
+        return Ok(value.clone());
+    }
+    let value = f();
+    map.insert(name.into(), value.clone());
+    map.save_in(backend, app, key)?;
+    Ok(value)
+}
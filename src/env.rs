@@ -0,0 +1,233 @@
+use crate::{Backend, Capabilities, PreferencesError};
+use app_dirs::AppInfo;
+use serde_json::Value;
+use std::env;
+use std::io::{self, ErrorKind};
+
+/// Read-only backend resolving preferences keys from environment variables.
+///
+/// The variable name is built from a prefix (by default, the app name) followed by the key, with
+/// every character that isn't an ASCII letter or digit replaced by an underscore, all in upper
+/// case. For example, the key `options/graphics` of an app named `MyApp` is read from
+/// `MYAPP_OPTIONS_GRAPHICS`.
+///
+/// Variable values are parsed as JSON. Anything that isn't valid JSON is treated as a plain
+/// string, so `MYAPP_THEME=dark` loads as `"dark"` while `MYAPP_VOLUME=11` loads as a number.
+///
+/// ```
+/// use preferences::{AppInfo, EnvBackend, Preferences};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// std::env::set_var("PREFERENCES_DOCS_ENV_THEME", "dark");
+/// let theme = String::load_in(&EnvBackend::new(), &APP_INFO, "docs/env-theme");
+/// assert_eq!(theme.unwrap(), "dark");
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EnvBackend {
+    prefix: Option<String>,
+}
+
+impl EnvBackend {
+    /// Creates a backend using the app name as the variable prefix.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self { prefix: None }
+    }
+
+    /// Creates a backend using a custom variable prefix instead of the app name.
+    #[must_use]
+    pub fn with_prefix<S: Into<String>>(prefix: S) -> Self {
+        Self {
+            prefix: Some(prefix.into()),
+        }
+    }
+
+    /// Returns the name of the environment variable that stores `key`.
+    #[must_use]
+    pub fn var_name(&self, app: &AppInfo, key: &str) -> String {
+        let prefix = self.prefix.as_deref().unwrap_or(app.name);
+        let mut name = String::with_capacity(prefix.len() + key.len() + 1);
+        push_sanitized(&mut name, prefix);
+        name.push('_');
+        push_sanitized(&mut name, key);
+        name
+    }
+}
+
+fn push_sanitized(name: &mut String, part: &str) {
+    name.extend(part.chars().map(|c| {
+        if c.is_ascii_alphanumeric() {
+            c.to_ascii_uppercase()
+        } else {
+            '_'
+        }
+    }));
+}
+
+impl Backend for EnvBackend {
+    fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        let name = self.var_name(app, key);
+        let value = env::var(&name).map_err(|e| {
+            let kind = match e {
+                env::VarError::NotPresent => ErrorKind::NotFound,
+                env::VarError::NotUnicode(_) => ErrorKind::InvalidData,
+            };
+            io::Error::new(kind, format!("{name}: {e}"))
+        })?;
+        if serde_json::from_str::<serde_json::Value>(&value).is_ok() {
+            Ok(value.into_bytes())
+        } else {
+            Ok(serde_json::to_vec(&value)?)
+        }
+    }
+    fn write(&self, _app: &AppInfo, key: &str, _data: &[u8]) -> Result<(), PreferencesError> {
+        Err(PreferencesError::ReadOnly(key.into()))
+    }
+}
+
+/// Wrapper letting environment variables override individual fields of the data loaded from
+/// another backend.
+///
+/// Each field of the loaded JSON object is looked up in an environment variable named like for
+/// an [`EnvBackend`](struct.EnvBackend.html), followed by the field name: the field `vsync` of
+/// the key `options/graphics` of an app named `MyApp` is overridden by
+/// `MYAPP_OPTIONS_GRAPHICS_VSYNC`. Fields of nested objects add their names in turn. Values are
+/// parsed the same way as for an `EnvBackend`.
+///
+/// Only fields present in the loaded data can be overridden. Overrides only last as long as the
+/// variables are set: when saving, a field that still has its overridden value is written with
+/// the value saved in the wrapped backend instead, so that e.g. a one-off `MYAPP_VOLUME=0`
+/// doesn't become permanent. Fields changed since loading are saved as usual.
+///
+/// ```
+/// use preferences::{AppInfo, EnvOverrides, FileBackend, Preferences, PreferencesMap};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let mut graphics = PreferencesMap::new();
+/// graphics.insert("vsync".to_owned(), true);
+/// graphics.save(&APP_INFO, "tests/docs/env-overrides").unwrap();
+///
+/// std::env::set_var("PREFERENCES_TESTS_DOCS_ENV_OVERRIDES_VSYNC", "false");
+/// let backend = EnvOverrides::new(FileBackend::new());
+/// let graphics = PreferencesMap::<bool>::load_in(&backend, &APP_INFO, "tests/docs/env-overrides");
+/// assert!(!graphics.unwrap()["vsync"]);
+/// ```
+#[derive(Clone, Debug, Default)]
+pub struct EnvOverrides<B> {
+    inner: B,
+    env: EnvBackend,
+}
+
+impl<B: Backend> EnvOverrides<B> {
+    /// Wraps `backend`, using the app name as the variable prefix.
+    #[must_use]
+    pub const fn new(backend: B) -> Self {
+        Self {
+            inner: backend,
+            env: EnvBackend::new(),
+        }
+    }
+
+    /// Wraps `backend`, using a custom variable prefix instead of the app name.
+    #[must_use]
+    pub fn with_prefix<S: Into<String>>(backend: B, prefix: S) -> Self {
+        Self {
+            inner: backend,
+            env: EnvBackend::with_prefix(prefix),
+        }
+    }
+
+    /// Unwraps this wrapper, returning the underlying backend.
+    #[must_use]
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+/// Replaces the fields of `value` for which a variable named `name` followed by the field name
+/// is set, and returns `true` if any was.
+fn override_fields(value: &mut Value, name: &mut String) -> bool {
+    let mut changed = false;
+    if let Value::Object(ref mut fields) = *value {
+        for (field, value) in fields {
+            let len = name.len();
+            name.push('_');
+            push_sanitized(name, field);
+            if let Ok(var) = env::var(&*name) {
+                *value = parse_var(var);
+                changed = true;
+            } else {
+                changed |= override_fields(value, name);
+            }
+            name.truncate(len);
+        }
+    }
+    changed
+}
+
+/// Puts back the `stored` value of the fields of `value` that still have the value of the
+/// variable overriding them, and returns `true` if any did.
+fn restore_fields(value: &mut Value, stored: &Value, name: &mut String) -> bool {
+    let mut changed = false;
+    if let (Value::Object(ref mut fields), Value::Object(ref stored)) = (value, stored) {
+        for (field, value) in fields {
+            let stored = match stored.get(field) {
+                Some(stored) => stored,
+                None => continue,
+            };
+            let len = name.len();
+            name.push('_');
+            push_sanitized(name, field);
+            if let Ok(var) = env::var(&*name) {
+                if same_value(value, &parse_var(var)) {
+                    *value = stored.clone();
+                    changed = true;
+                }
+            } else {
+                changed |= restore_fields(value, stored, name);
+            }
+            name.truncate(len);
+        }
+    }
+    changed
+}
+
+/// Parses a variable value as JSON, or as a plain string if it isn't valid JSON.
+fn parse_var(var: String) -> Value {
+    serde_json::from_str(&var).unwrap_or(Value::String(var))
+}
+
+/// Compares two values, treating numbers that only differ by their representation (`0` and
+/// `0.0`, or an `f32` widened to an `f64`) as equal.
+fn same_value(a: &Value, b: &Value) -> bool {
+    match (a.as_f64(), b.as_f64()) {
+        (Some(a), Some(b)) => (a - b).abs() <= f64::from(f32::EPSILON) * a.abs().max(b.abs()),
+        _ => a == b,
+    }
+}
+
+impl<B: Backend> Backend for EnvOverrides<B> {
+    fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        let data = self.inner.read(app, key)?;
+        let mut value = serde_json::from_slice(&data)?;
+        if override_fields(&mut value, &mut self.env.var_name(app, key)) {
+            Ok(serde_json::to_vec(&value)?)
+        } else {
+            Ok(data)
+        }
+    }
+    fn write(&self, app: &AppInfo, key: &str, data: &[u8]) -> Result<(), PreferencesError> {
+        let stored = self.inner.read(app, key).ok();
+        let stored = stored.and_then(|stored| serde_json::from_slice(&stored).ok());
+        let value = serde_json::from_slice(data).ok();
+        if let (Some(mut value), Some(stored)) = (value, stored) {
+            if restore_fields(&mut value, &stored, &mut self.env.var_name(app, key)) {
+                return self.inner.write(app, key, &serde_json::to_vec(&value)?);
+            }
+        }
+        self.inner.write(app, key, data)
+    }
+    fn capabilities(&self) -> Capabilities {
+        self.inner.capabilities()
+    }
+}
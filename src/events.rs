@@ -0,0 +1,85 @@
+use crate::metadata::{crc32, unix_time};
+use crate::PreferencesError;
+use serde::{Deserialize, Serialize};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, ErrorKind, Write};
+use std::path::Path;
+use std::process;
+
+/// What happened to a key, in a [`ChangeEvent`](struct.ChangeEvent.html).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+#[non_exhaustive]
+pub enum ChangeKind {
+    /// New data was saved under the key.
+    Save,
+    /// The data saved under the key was deleted (or moved aside).
+    Delete,
+}
+
+/// Entry of the event log kept by a [`FileBackend`](struct.FileBackend.html) configured with
+/// [`with_event_log`](struct.FileBackend.html#method.with_event_log).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct ChangeEvent {
+    /// Key that changed.
+    pub key: String,
+    /// What happened to the key.
+    pub kind: ChangeKind,
+    /// Time of the change, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Size of the saved data, in bytes (zero for deletions).
+    pub size: u64,
+    /// CRC-32 of the saved data, or `None` for deletions.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>,
+    /// ID of the process that made the change.
+    pub process_id: u32,
+    /// Version of the application that made the change, if the backend was configured with
+    /// [`with_provenance`](struct.FileBackend.html#method.with_provenance).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub app_version: Option<String>,
+}
+
+impl ChangeEvent {
+    /// Describes a change happening now: a save of `data`, or a deletion if `data` is `None`.
+    pub(crate) fn now(key: &str, data: Option<&[u8]>, app_version: Option<&str>) -> Self {
+        Self {
+            key: key.into(),
+            kind: data.map_or(ChangeKind::Delete, |_| ChangeKind::Save),
+            timestamp: unix_time(),
+            size: data.map_or(0, |data| data.len() as u64),
+            checksum: data.map(crc32),
+            process_id: process::id(),
+            app_version: app_version.map(Into::into),
+        }
+    }
+}
+
+/// Appends `event` to the log at `path`, as one line of JSON.
+pub fn append(path: &Path, event: &ChangeEvent) -> Result<(), PreferencesError> {
+    let mut line = serde_json::to_vec(event)?;
+    line.push(b'\n');
+    // A single small write to a file opened for appending is never interleaved with others.
+    let mut file = OpenOptions::new().create(true).append(true).open(path)?;
+    Ok(file.write_all(&line)?)
+}
+
+/// Reads the last `limit` events of the log at `path`, oldest first.
+///
+/// Lines that can't be parsed (e.g. cut short by a crash) are skipped.
+pub fn read_recent(path: &Path, limit: usize) -> Result<Vec<ChangeEvent>, PreferencesError> {
+    let file = match File::open(path) {
+        Err(e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+        file => file?,
+    };
+    let mut events = Vec::new();
+    for line in BufReader::new(file).lines() {
+        if let Ok(event) = serde_json::from_str(&line?) {
+            events.push(event);
+        }
+    }
+    let skip = events.len().saturating_sub(limit);
+    events.drain(..skip);
+    Ok(events)
+}
@@ -0,0 +1,88 @@
+use crate::PreferencesError;
+use std::fmt;
+use std::sync::{Arc, Weak};
+
+/// Something holding saves that haven't been written yet, such as a
+/// [`PrefsSaver`](struct.PrefsSaver.html) or an [`AutoSave`](struct.AutoSave.html).
+pub trait Flush {
+    /// Waits until every pending save has been written.
+    ///
+    /// # Errors
+    /// If any pending save failed, returns the first error encountered.
+    fn flush(&self) -> Result<(), PreferencesError>;
+}
+
+/// Guard flushing background saves when it goes out of scope.
+///
+/// Background savers write their pending changes when they are dropped, but that never happens
+/// to a saver still shared with other parts of the program (or leaked) when `main` returns.
+/// Create this guard at the top of `main`, register every saver with it, and the last changes
+/// are written on the way out, however the savers are shared. The guard only holds weak
+/// references, so it doesn't keep the savers alive.
+///
+/// Destructors don't run on `std::process::exit`, so call [`flush`](#method.flush) before
+/// exiting that way.
+///
+/// ```
+/// use preferences::{AppInfo, FlushGuard, Preferences, PrefsSaver};
+/// use std::sync::Arc;
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let mut guard = FlushGuard::new();
+/// let saver = Arc::new(PrefsSaver::new());
+/// guard.register(&saver);
+/// saver.save(&42, &APP_INFO, "tests/docs/flush-guard").unwrap();
+/// std::mem::forget(saver); // Never dropped (e.g. still shared with a running thread)...
+/// drop(guard); // ...but still flushed.
+/// assert_eq!(i32::load(&APP_INFO, "tests/docs/flush-guard").unwrap(), 42);
+/// ```
+#[derive(Default)]
+pub struct FlushGuard {
+    targets: Vec<Weak<dyn Flush>>,
+}
+
+impl FlushGuard {
+    /// Creates a guard with nothing to flush.
+    #[must_use]
+    pub const fn new() -> Self {
+        Self {
+            targets: Vec::new(),
+        }
+    }
+
+    /// Flushes `target` when this guard is dropped, if it still exists by then.
+    pub fn register<T: Flush + 'static>(&mut self, target: &Arc<T>) -> &mut Self {
+        let target: Arc<dyn Flush> = Arc::clone(target) as _;
+        self.targets.push(Arc::downgrade(&target));
+        self
+    }
+
+    /// Flushes every registered saver that still exists, in the order they were registered.
+    ///
+    /// # Errors
+    /// If any pending save failed, returns the first error encountered. Every saver is flushed
+    /// regardless.
+    pub fn flush(&self) -> Result<(), PreferencesError> {
+        let mut result = Ok(());
+        for target in self.targets.iter().filter_map(Weak::upgrade) {
+            if let Err(e) = target.flush() {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
+
+impl Drop for FlushGuard {
+    fn drop(&mut self) {
+        let _ = self.flush();
+    }
+}
+
+impl fmt::Debug for FlushGuard {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("FlushGuard")
+            .field("targets", &self.targets.len())
+            .finish()
+    }
+}
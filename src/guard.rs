@@ -0,0 +1,130 @@
+use crate::{Backend, FileBackend, Preferences, PreferencesError};
+use app_dirs::AppInfo;
+use std::fmt;
+use std::ops::{Deref, DerefMut};
+use std::thread;
+
+/// Guard that loads a value and saves it back when dropped.
+///
+/// The value is only saved if it was accessed mutably. Errors can't be reported from a drop, so
+/// a failed save on drop is ignored: call [`commit`](#method.commit) to save and find out whether
+/// it worked, or [`discard`](#method.discard) to drop the changes instead. Nothing is saved if the
+/// guard is dropped while its thread is panicking, since the value may be half-modified then.
+///
+/// ```
+/// use preferences::{AppInfo, Preferences, PreferencesMap, PrefsGuard};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let key = "tests/docs/guard";
+/// {
+///     let mut prefs = PrefsGuard::<PreferencesMap<u32>>::load_or_default(&APP_INFO, key).unwrap();
+///     prefs.insert("volume".into(), 7);
+/// } // Saved here
+/// assert_eq!(PreferencesMap::<u32>::load(&APP_INFO, key).unwrap()["volume"], 7);
+/// ```
+pub struct PrefsGuard<T: Preferences, B: Backend = FileBackend> {
+    value: T,
+    backend: B,
+    app: AppInfo,
+    key: String,
+    touched: bool,
+}
+
+impl<T: Preferences> PrefsGuard<T> {
+    /// Loads the value saved under `key` in the default [`FileBackend`](struct.FileBackend.html).
+    ///
+    /// # Errors
+    /// If a deserialization or file I/O error occurs, or if no data exists for that `key`.
+    pub fn load<S: Into<String>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError> {
+        Self::load_in(FileBackend::new(), app, key)
+    }
+
+    /// Same as `load`, but starts from the default value if no data exists for that `key`.
+    ///
+    /// # Errors
+    /// If the data exists but can't be read or deserialized.
+    pub fn load_or_default<S: Into<String>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError>
+    where
+        T: Default,
+    {
+        let key = key.into();
+        let value = match T::load(app, &key) {
+            Err(e) if e.is_not_found() => T::default(),
+            result => result?,
+        };
+        Ok(Self::new(value, FileBackend::new(), app, key))
+    }
+}
+
+impl<T: Preferences, B: Backend> PrefsGuard<T, B> {
+    /// Same as `load`, but uses `backend`.
+    ///
+    /// # Errors
+    /// If a deserialization error occurs, or if the backend fails to read the data.
+    pub fn load_in<S: Into<String>>(
+        backend: B,
+        app: &AppInfo,
+        key: S,
+    ) -> Result<Self, PreferencesError> {
+        let key = key.into();
+        let value = T::load_in(&backend, app, &key)?;
+        Ok(Self::new(value, backend, app, key))
+    }
+
+    fn new(value: T, backend: B, app: &AppInfo, key: String) -> Self {
+        Self {
+            value,
+            backend,
+            app: app.clone(),
+            key,
+            touched: false,
+        }
+    }
+
+    /// Saves the value now, whether or not it was changed, and returns the result.
+    ///
+    /// # Errors
+    /// If a serialization error occurs, or if the backend fails to write the data.
+    pub fn commit(mut self) -> Result<(), PreferencesError> {
+        self.touched = false;
+        self.value.save_in(&self.backend, &self.app, &self.key)
+    }
+
+    /// Drops the guard without saving the value.
+    pub fn discard(mut self) {
+        self.touched = false;
+    }
+}
+
+impl<T: Preferences, B: Backend> Deref for PrefsGuard<T, B> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T: Preferences, B: Backend> DerefMut for PrefsGuard<T, B> {
+    fn deref_mut(&mut self) -> &mut T {
+        self.touched = true;
+        &mut self.value
+    }
+}
+
+impl<T: Preferences, B: Backend> Drop for PrefsGuard<T, B> {
+    fn drop(&mut self) {
+        if self.touched && !thread::panicking() {
+            let _ = self.value.save_in(&self.backend, &self.app, &self.key);
+        }
+    }
+}
+
+impl<T: Preferences + fmt::Debug, B: Backend> fmt::Debug for PrefsGuard<T, B> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PrefsGuard")
+            .field("value", &self.value)
+            .field("app", &self.app)
+            .field("key", &self.key)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,133 @@
+use crate::{Backend, FileBackend, Preferences, PreferencesError, PreferencesMap};
+use app_dirs::AppInfo;
+use std::fmt;
+use std::sync::mpsc::{channel, Receiver};
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+/// Delivers the saved data to one subscriber, returning `false` once the subscriber is gone.
+type Subscriber = Box<dyn Fn(&[u8]) -> bool + Send>;
+
+struct Inner {
+    backend: Box<dyn Backend + Send + Sync>,
+    app: AppInfo,
+    subscribers: Mutex<PreferencesMap<Vec<Subscriber>>>,
+}
+
+/// Shared access to an app's user data, telling subscribers whenever a key is saved.
+///
+/// Clones of a handle share the same subscribers, so different parts of a program can pass
+/// handles around and react to each other's changes, like a small settings bus. Every save made
+/// through any clone is delivered to the subscribers of that key, as a freshly deserialized
+/// value sent over a channel. Changes made without going through a handle (e.g. by another
+/// process) aren't reported; see [`ChangeStream`](struct.ChangeStream.html) for those.
+///
+/// ```
+/// use preferences::{AppInfo, PrefsHandle};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let prefs = PrefsHandle::new(&APP_INFO);
+/// let volume_changes = prefs.subscribe::<u8, _>("tests/docs/handle/volume");
+///
+/// // Elsewhere in the program...
+/// prefs.clone().save(&11, "tests/docs/handle/volume").unwrap();
+///
+/// assert_eq!(volume_changes.recv().unwrap().unwrap(), 11);
+/// ```
+#[derive(Clone)]
+pub struct PrefsHandle {
+    inner: Arc<Inner>,
+}
+
+impl PrefsHandle {
+    /// Creates a handle to the user data of `app` in the default
+    /// [`FileBackend`](struct.FileBackend.html).
+    #[must_use]
+    pub fn new(app: &AppInfo) -> Self {
+        Self::with_backend(FileBackend::new(), app)
+    }
+
+    /// Creates a handle to the user data of `app` in `backend`.
+    #[must_use]
+    pub fn with_backend<B: Backend + Send + Sync + 'static>(backend: B, app: &AppInfo) -> Self {
+        Self {
+            inner: Arc::new(Inner {
+                backend: Box::new(backend),
+                app: app.clone(),
+                subscribers: Mutex::new(PreferencesMap::new()),
+            }),
+        }
+    }
+
+    /// Saves `value` under `key`, then sends it to every subscriber of `key`.
+    ///
+    /// # Errors
+    /// If a serialization error occurs, or if the backend fails to write the data. Subscribers
+    /// aren't notified in that case.
+    pub fn save<T, S>(&self, value: &T, key: S) -> Result<(), PreferencesError>
+    where
+        T: Preferences,
+        S: AsRef<str>,
+    {
+        let key = key.as_ref();
+        let data = value.serialized()?;
+        self.inner.backend.write(&self.inner.app, key, &data)?;
+        let mut subscribers = self.subscribers();
+        if let Some(key_subscribers) = subscribers.get_mut(key) {
+            key_subscribers.retain(|subscriber| subscriber(&data));
+            if key_subscribers.is_empty() {
+                subscribers.remove(key);
+            }
+        }
+        drop(subscribers);
+        Ok(())
+    }
+
+    /// Loads the value saved under `key`.
+    ///
+    /// # Errors
+    /// If a deserialization error occurs, or if the backend fails to read the data.
+    pub fn load<T: Preferences, S: AsRef<str>>(&self, key: S) -> Result<T, PreferencesError> {
+        T::load_in(&*self.inner.backend, &self.inner.app, key)
+    }
+
+    /// Subscribes to the saves of `key` made through this handle or its clones.
+    ///
+    /// Each save sends the saved value, deserialized as a `T`, or the error that occurred while
+    /// deserializing it. Drop the receiver to unsubscribe.
+    #[must_use]
+    pub fn subscribe<T, S>(&self, key: S) -> Receiver<Result<T, PreferencesError>>
+    where
+        T: Preferences + Send + 'static,
+        S: AsRef<str>,
+    {
+        let (sender, receiver) = channel();
+        let subscriber: Subscriber =
+            Box::new(move |mut data: &[u8]| sender.send(T::load_from(&mut data)).is_ok());
+        self.subscribers()
+            .entry(key.as_ref().into())
+            .or_default()
+            .push(subscriber);
+        receiver
+    }
+
+    /// Returns the app whose user data this handle accesses.
+    #[must_use]
+    pub fn app(&self) -> &AppInfo {
+        &self.inner.app
+    }
+
+    fn subscribers(&self) -> MutexGuard<'_, PreferencesMap<Vec<Subscriber>>> {
+        self.inner
+            .subscribers
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl fmt::Debug for PrefsHandle {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PrefsHandle")
+            .field("app", &self.inner.app)
+            .finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,113 @@
+use serde::de::{DeserializeOwned, Deserializer, Error as _};
+use serde::ser::{Error as _, Serializer};
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value};
+use std::ops::{Deref, DerefMut};
+
+/// Wrapper that keeps the fields of saved data that `T` doesn't know about, and writes them back
+/// when saved.
+///
+/// Saving a struct normally drops every field it doesn't have. When an older release of an app
+/// loads and saves data written by a newer one (e.g. after a user rolls back), the fields added in
+/// the newer release are lost. Loading and saving through `KeepUnknown<T>` instead keeps them.
+///
+/// Only top-level fields of JSON objects are kept. Fields that `T` skips when serializing count
+/// as unknown, so their saved value is kept too.
+///
+/// ```
+/// use preferences::{AppInfo, KeepUnknown, Preferences, PreferencesMap};
+/// use serde::{Deserialize, Serialize};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Audio {
+///     volume: u32,
+/// }
+///
+/// let key = "tests/docs/keep-unknown";
+/// let mut newer = PreferencesMap::new();
+/// newer.insert("volume".to_owned(), 5);
+/// newer.insert("balance".to_owned(), -2);
+/// newer.save(&APP_INFO, key).unwrap();
+///
+/// let mut audio = KeepUnknown::<Audio>::load(&APP_INFO, key).unwrap();
+/// audio.volume = 8;
+/// audio.save(&APP_INFO, key).unwrap();
+///
+/// let saved = PreferencesMap::<i32>::load(&APP_INFO, key).unwrap();
+/// assert_eq!(saved["volume"], 8);
+/// assert_eq!(saved["balance"], -2);
+/// ```
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct KeepUnknown<T> {
+    value: T,
+    unknown: Map<String, Value>,
+}
+
+impl<T> KeepUnknown<T> {
+    /// Wraps `value`, without any unknown fields.
+    #[must_use]
+    pub fn new(value: T) -> Self {
+        Self {
+            value,
+            unknown: Map::new(),
+        }
+    }
+
+    /// Returns the fields kept from the loaded data.
+    #[must_use]
+    pub const fn unknown(&self) -> &Map<String, Value> {
+        &self.unknown
+    }
+
+    /// Unwraps this wrapper, dropping the unknown fields.
+    #[must_use]
+    pub fn into_inner(self) -> T {
+        self.value
+    }
+}
+
+impl<T> Deref for KeepUnknown<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.value
+    }
+}
+
+impl<T> DerefMut for KeepUnknown<T> {
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.value
+    }
+}
+
+impl<T: Serialize> Serialize for KeepUnknown<T> {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        if self.unknown.is_empty() {
+            return self.value.serialize(serializer);
+        }
+        let mut value = serde_json::to_value(&self.value).map_err(S::Error::custom)?;
+        if let Value::Object(ref mut fields) = value {
+            for (name, field) in &self.unknown {
+                fields.entry(name.clone()).or_insert_with(|| field.clone());
+            }
+        }
+        value.serialize(serializer)
+    }
+}
+
+impl<'de, T: Serialize + DeserializeOwned> Deserialize<'de> for KeepUnknown<T> {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let saved = Value::deserialize(deserializer)?;
+        let value = T::deserialize(&saved).map_err(D::Error::custom)?;
+        // The fields `T` knows about are the ones it writes back.
+        let unknown = match (saved, serde_json::to_value(&value)) {
+            (Value::Object(fields), Ok(Value::Object(known))) => fields
+                .into_iter()
+                .filter(|(name, _)| !known.contains_key(name))
+                .collect(),
+            _ => Map::new(),
+        };
+        Ok(Self { value, unknown })
+    }
+}
@@ -0,0 +1,84 @@
+use crate::{Backend, Capabilities, PreferencesError};
+use app_dirs::AppInfo;
+use std::io::{self, ErrorKind};
+
+/// Backend chaining several other backends in order of precedence.
+///
+/// Reads go to each layer in the order they were added, returning the data from the first layer
+/// that has the key. Writes only go to the layer added with
+/// [`writable_layer`](#method.writable_layer); without one, saving fails with
+/// [`PreferencesError::ReadOnly`](enum.PreferencesError.html#variant.ReadOnly).
+///
+/// ```
+/// use preferences::{AppInfo, EnvBackend, FileBackend, LayeredBackend, Preferences};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// // Environment variables override the user's files, which are also where saves go.
+/// let backend = LayeredBackend::new()
+///     .layer(EnvBackend::new())
+///     .writable_layer(FileBackend::new());
+///
+/// let key = "tests/docs/layered";
+/// assert!(42.save_in(&backend, &APP_INFO, key).is_ok());
+/// assert_eq!(i32::load_in(&backend, &APP_INFO, key).unwrap(), 42);
+/// ```
+#[derive(Default)]
+pub struct LayeredBackend {
+    layers: Vec<Box<dyn Backend + Send + Sync>>,
+    writable: Option<usize>,
+}
+
+impl LayeredBackend {
+    /// Creates a backend with no layers.
+    #[must_use]
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Adds a read-only layer, with lower precedence than the layers already added.
+    #[must_use]
+    pub fn layer<B: Backend + Send + Sync + 'static>(mut self, backend: B) -> Self {
+        self.layers.push(Box::new(backend));
+        self
+    }
+
+    /// Adds a layer, with lower precedence than the layers already added, and makes it the
+    /// layer that receives all writes.
+    #[must_use]
+    pub fn writable_layer<B: Backend + Send + Sync + 'static>(mut self, backend: B) -> Self {
+        self.writable = Some(self.layers.len());
+        self.layer(backend)
+    }
+}
+
+impl Backend for LayeredBackend {
+    fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        for layer in &self.layers {
+            match layer.read(app, key) {
+                Err(e) if e.is_not_found() => {}
+                result => return result,
+            }
+        }
+        let msg = format!("{key}: not found in any layer");
+        Err(io::Error::new(ErrorKind::NotFound, msg).into())
+    }
+    fn write(&self, app: &AppInfo, key: &str, data: &[u8]) -> Result<(), PreferencesError> {
+        let layer = self.writable.map(|index| &self.layers[index]);
+        let layer = layer.ok_or_else(|| PreferencesError::ReadOnly(key.into()))?;
+        layer.write(app, key, data)
+    }
+    /// Write guarantees come from the writable layer; the others only hold if every layer
+    /// offers them.
+    fn capabilities(&self) -> Capabilities {
+        let mut capabilities = self
+            .writable
+            .map(|index| self.layers[index].capabilities())
+            .unwrap_or_default();
+        for layer in &self.layers {
+            let layer = layer.capabilities();
+            capabilities.watchable &= layer.watchable;
+            capabilities.encrypted_at_rest &= layer.encrypted_at_rest;
+        }
+        capabilities
+    }
+}
@@ -0,0 +1,189 @@
+use crate::{merge, FileBackend, MergeStrategy, Preferences, PreferencesError, Scope};
+use app_dirs::AppInfo;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+
+/// Where a value of a [`LayeredPreferences`](struct.LayeredPreferences.html) comes from, from
+/// lowest to highest precedence.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum Layer {
+    /// Machine-wide defaults, e.g. provisioned by an administrator.
+    System,
+    /// The user's own preferences.
+    User,
+    /// Overrides set by the running program, which are never saved.
+    Override,
+}
+
+impl Layer {
+    const ALL: [Self; 3] = [Self::System, Self::User, Self::Override];
+}
+
+/// Value combined from machine-wide defaults, the user's preferences, and in-process overrides.
+///
+/// Each layer is a JSON object holding some of the fields of `T`. A field set in a layer hides
+/// the same field in lower layers; nested objects are merged field by field. The combined object
+/// must then deserialize as `T`, so fields that no layer sets need a `#[serde(default)]`.
+///
+/// Changes made with [`set`](#method.set) go to the user layer, which is the only one that
+/// [`save`](#method.save) writes.
+///
+/// ```
+/// use preferences::{AppInfo, Layer, LayeredPreferences, Preferences};
+/// use serde::{Deserialize, Serialize};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Settings {
+///     #[serde(default)]
+///     telemetry: bool,
+///     #[serde(default)]
+///     theme: String,
+/// }
+///
+/// let mut settings = LayeredPreferences::<Settings>::load(&APP_INFO, "tests/docs/layered-prefs")
+///     .unwrap();
+/// settings.set("theme", "dark").unwrap();
+/// settings.set_override("telemetry", false).unwrap(); // e.g. from a command-line flag
+/// assert_eq!(settings.get().theme, "dark");
+/// assert_eq!(settings.source("theme"), Some(Layer::User));
+/// assert_eq!(settings.source("telemetry"), Some(Layer::Override));
+/// settings.save().unwrap();
+/// ```
+#[derive(Debug)]
+pub struct LayeredPreferences<T> {
+    user: FileBackend,
+    app: AppInfo,
+    key: String,
+    layers: [Map<String, Value>; 3],
+    value: T,
+}
+
+impl<T: Serialize + DeserializeOwned> LayeredPreferences<T> {
+    /// Loads the machine-wide and user layers saved under `key`, from the
+    /// [`Scope::System`](enum.Scope.html) and [`Scope::User`](enum.Scope.html) directories.
+    ///
+    /// # Errors
+    /// If a layer exists but can't be read or isn't a JSON object, or if the combined layers
+    /// don't deserialize as `T`.
+    pub fn load<S: AsRef<str>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError> {
+        let system = FileBackend::with_scope(Scope::System);
+        Self::with_backends(&system, FileBackend::new(), app, key)
+    }
+
+    /// Same as `load`, but reads the machine-wide layer from `system`, and reads and saves the
+    /// user layer in `user`.
+    ///
+    /// # Errors
+    /// If a layer exists but can't be read or isn't a JSON object, or if the combined layers
+    /// don't deserialize as `T`.
+    pub fn with_backends<S: AsRef<str>>(
+        system: &FileBackend,
+        user: FileBackend,
+        app: &AppInfo,
+        key: S,
+    ) -> Result<Self, PreferencesError> {
+        let key = key.as_ref();
+        let load = |backend: &FileBackend| match Map::load_in(backend, app, key) {
+            Err(e) if e.is_not_found() => Ok(Map::new()),
+            result => result,
+        };
+        let layers = [load(system)?, load(&user)?, Map::new()];
+        let value = combine(&layers)?;
+        Ok(Self {
+            user,
+            app: app.clone(),
+            key: key.into(),
+            layers,
+            value,
+        })
+    }
+
+    /// Sets `field` in the user layer.
+    ///
+    /// # Errors
+    /// If `value` can't be serialized, or if the result doesn't deserialize as `T`. The layer is
+    /// left unchanged in that case.
+    pub fn set<V: Serialize>(&mut self, field: &str, value: V) -> Result<(), PreferencesError> {
+        let value = serde_json::to_value(value)?;
+        self.change(Layer::User, |layer| {
+            layer.insert(field.into(), value);
+        })
+    }
+
+    /// Removes `field` from the user layer, so that it falls back to the machine-wide default.
+    ///
+    /// # Errors
+    /// If the result doesn't deserialize as `T`. The layer is left unchanged in that case.
+    pub fn reset(&mut self, field: &str) -> Result<(), PreferencesError> {
+        self.change(Layer::User, |layer| {
+            layer.remove(field);
+        })
+    }
+
+    /// Sets `field` in the override layer, for the lifetime of this value.
+    ///
+    /// # Errors
+    /// If `value` can't be serialized, or if the result doesn't deserialize as `T`. The layer is
+    /// left unchanged in that case.
+    pub fn set_override<V: Serialize>(
+        &mut self,
+        field: &str,
+        value: V,
+    ) -> Result<(), PreferencesError> {
+        let value = serde_json::to_value(value)?;
+        self.change(Layer::Override, |layer| {
+            layer.insert(field.into(), value);
+        })
+    }
+
+    /// Saves the user layer.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs.
+    pub fn save(&self) -> Result<(), PreferencesError> {
+        self.layers[Layer::User as usize].save_in(&self.user, &self.app, &self.key)
+    }
+
+    fn change<F>(&mut self, layer: Layer, change: F) -> Result<(), PreferencesError>
+    where
+        F: FnOnce(&mut Map<String, Value>),
+    {
+        let mut layers = self.layers.clone();
+        change(&mut layers[layer as usize]);
+        self.value = combine(&layers)?;
+        self.layers = layers;
+        Ok(())
+    }
+}
+
+impl<T> LayeredPreferences<T> {
+    /// Returns the combined value.
+    #[must_use]
+    pub const fn get(&self) -> &T {
+        &self.value
+    }
+
+    /// Returns the highest layer that sets `field`, or `None` if no layer does.
+    #[must_use]
+    pub fn source(&self, field: &str) -> Option<Layer> {
+        Layer::ALL
+            .iter()
+            .rev()
+            .copied()
+            .find(|&layer| self.layers[layer as usize].contains_key(field))
+    }
+}
+
+fn combine<T: DeserializeOwned>(layers: &[Map<String, Value>]) -> Result<T, PreferencesError> {
+    let mut combined = Value::Object(Map::new());
+    for layer in layers {
+        merge(
+            &mut combined,
+            Value::Object(layer.clone()),
+            MergeStrategy::Deep,
+        );
+    }
+    Ok(serde_json::from_value(combined)?)
+}
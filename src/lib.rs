@@ -1,5 +1,5 @@
 #![warn(clippy::all, clippy::pedantic, clippy::nursery, clippy::cargo)]
-#![allow(clippy::enum_glob_use)]
+#![allow(clippy::enum_glob_use, clippy::multiple_crate_versions)]
 //! *Read and write user-specific application data*
 //!
 //! This crate allows Rust developers to store and retrieve user-local preferences and other
@@ -20,6 +20,9 @@
 //! will be implemented for your map instance. This allows you to seamlessly save and load
 //! user data with the `save(..)` and `load(..)` trait methods from `Preferences`.
 //!
+//! `use preferences::prelude::*;` imports these along with the other commonly used items and
+//! traits; see the [`prelude`](prelude/index.html) module.
+//!
 //! # Basic example
 //! ```
 //! extern crate preferences;
@@ -159,6 +162,27 @@
 //! * Better adoption rates and language compatibility than e.g. TOML
 //! * Not reliant on a consistent memory layout like e.g. binary
 //!
+//! # Optional features
+//! * `indexmap`: [`OrderedPreferencesMap`](type.OrderedPreferencesMap.html), a variant of
+//!   `PreferencesMap` that saves its entries in a stable order.
+//! * `async`: the [`AsyncPreferences`](trait.AsyncPreferences.html) trait, for saving and loading
+//!   from async code without blocking the executor, the
+//!   [`AsyncBackend`](trait.AsyncBackend.html) trait, for storage that is asynchronous by
+//!   nature, and [`ChangeStream`](struct.ChangeStream.html), for awaiting changes to saved data.
+//! * `watch`: the [`watch`](fn.watch.html) function, for running a callback whenever saved data
+//!   changes on disk, [`Reloading`](struct.Reloading.html), for keeping the latest version of
+//!   saved data at hand, and (on Unix) [`ChangeNotifier`](struct.ChangeNotifier.html), for
+//!   telling other instances of an app about changes directly.
+//! * `clap`: [`ArgOverrides`](struct.ArgOverrides.html), for overriding loaded preferences with
+//!   command-line arguments parsed by `clap`.
+//! * `derive`: `#[derive(Preferences)]`, generating `load_default_key()` and
+//!   `save_default_key()` methods for a type saved under a fixed key.
+//! * `figment` and `config`: [`PrefsSource`](struct.PrefsSource.html), for using saved
+//!   preferences as a source in a `figment` or `config` pipeline, and `Backend` implementations
+//!   for `figment::Figment` and `config::Config`, for loading preferences from one.
+//! * `signing`: [`FileBackend::with_signing_key`](struct.FileBackend.html#method.with_signing_key),
+//!   for detecting saved data that was tampered with.
+//!
 //! You could, of course, implement `Preferences` yourself and store your user data in
 //! whatever location and format that you wanted. But that would defeat the purpose of this
 //! library. &#128522;
@@ -172,22 +196,114 @@ extern crate app_dirs;
 extern crate serde;
 extern crate serde_json;
 
-use app_dirs::{get_app_dir, get_data_root, AppDataType};
+pub mod prelude;
+
+#[cfg(feature = "clap")]
+mod args;
+#[cfg(feature = "async")]
+mod asynchronous;
+mod autosave;
+mod backend;
+mod bulk;
+#[cfg(feature = "async")]
+mod changes;
+mod crash;
+mod defaults;
+mod document;
+mod edit;
+mod entry;
+mod env;
+mod events;
+mod flush;
+mod guard;
+mod handle;
+mod keep_unknown;
+mod layered;
+mod layered_preferences;
+mod lock;
+mod merge;
+mod metadata;
+mod migrate;
+#[cfg(all(feature = "watch", unix))]
+mod notify_channel;
+mod prefs;
+mod profile;
+mod project;
+mod read_only;
+#[cfg(feature = "watch")]
+mod reloading;
+mod saver;
+#[cfg(feature = "signing")]
+mod signing;
+#[cfg(any(feature = "figment", feature = "config"))]
+mod source;
+mod transaction;
+mod trust;
+mod typed;
+#[cfg(feature = "watch")]
+mod watch;
+
+#[cfg(feature = "clap")]
+pub use crate::args::ArgOverrides;
+#[cfg(feature = "async")]
+pub use crate::asynchronous::{
+    AsyncBackend, AsyncPreferences, BlockOn, PreferencesFuture, Unblock,
+};
+pub use crate::autosave::{AutoSave, AutoSaveGuard};
+use crate::backend::base_dir_override;
+pub use crate::backend::{
+    Backend, Capabilities, Consistency, FileBackend, Revision, SaveOptions, Scope, BASE_DIR_ENV_VAR,
+};
+pub use crate::bulk::BulkReport;
+#[cfg(feature = "async")]
+pub use crate::changes::ChangeStream;
+pub use crate::crash::CrashMarker;
+pub use crate::defaults::BundledDefaults;
+pub use crate::document::PrefsDocument;
+pub use crate::edit::{edit, edit_in, update_field, update_field_in};
+pub use crate::entry::{get_or_insert_with, get_or_insert_with_in};
+pub use crate::env::{EnvBackend, EnvOverrides};
+pub use crate::events::{ChangeEvent, ChangeKind};
+pub use crate::flush::{Flush, FlushGuard};
+pub use crate::guard::PrefsGuard;
+pub use crate::handle::PrefsHandle;
+pub use crate::keep_unknown::KeepUnknown;
+pub use crate::layered::LayeredBackend;
+pub use crate::layered_preferences::{Layer, LayeredPreferences};
+pub use crate::lock::PrefsLockGuard;
+pub use crate::merge::{
+    apply_patch, apply_patch_in, merge, merge_maps, merge_patch, MergeStrategy,
+};
+pub use crate::metadata::{ContentType, Metadata, Provenance, VersionHeader};
+pub use crate::migrate::Migrator;
+#[cfg(all(feature = "watch", unix))]
+pub use crate::notify_channel::{ChangeListener, ChangeNotifier};
+pub use crate::prefs::{Prefs, PrefsEntry};
+pub use crate::profile::Profile;
+pub use crate::project::ProjectBackend;
+pub use crate::read_only::ReadOnly;
+#[cfg(feature = "watch")]
+pub use crate::reloading::Reloading;
+pub use crate::saver::PrefsSaver;
+#[cfg(any(feature = "figment", feature = "config"))]
+pub use crate::source::PrefsSource;
+pub use crate::transaction::Transaction;
+pub use crate::trust::{TrustLevel, TrustStore};
+pub use crate::typed::{ParseValueError, TypedPreferences};
+#[cfg(feature = "watch")]
+pub use crate::watch::{watch, PrefsWatcher};
+use app_dirs::{get_data_root, AppDataType};
 pub use app_dirs::{AppDirsError, AppInfo};
+#[cfg(feature = "derive")]
+pub use preferences_derive::Preferences;
 use serde::de::DeserializeOwned;
 use serde::Serialize;
 use std::collections::HashMap;
-use std::ffi::OsString;
 use std::fmt;
-use std::fs::{create_dir_all, File};
 use std::io::{self, ErrorKind, Read, Write};
 use std::path::PathBuf;
 use std::string::FromUtf8Error;
 
-const DATA_TYPE: AppDataType = AppDataType::UserConfig;
-static PREFS_FILE_EXTENSION: &str = ".prefs.json";
-static DEFAULT_PREFS_FILENAME: &str = "prefs.json";
-
 /// Generic key-value store for user data.
 ///
 /// This is actually a wrapper type around [`std::collections::HashMap<String, T>`][hashmap-api]
@@ -202,6 +318,19 @@ static DEFAULT_PREFS_FILENAME: &str = "prefs.json";
 /// [hashmap-api]: https://doc.rust-lang.org/std/collections/struct.HashMap.html
 pub type PreferencesMap<T = String> = HashMap<String, T>;
 
+/// Generic key-value store for user data that remembers insertion order. (Requires the `indexmap`
+/// feature.)
+///
+/// This is the same as [`PreferencesMap`](type.PreferencesMap.html), except that it's a wrapper
+/// type around [`indexmap::IndexMap<String, T>`][indexmap-api]. Entries are saved in the order
+/// they were inserted (and loaded in the order they were saved), so saved files don't get
+/// reshuffled from one run to the next. This keeps diffs of saved files meaningful and makes
+/// their contents reproducible.
+///
+/// [indexmap-api]: https://docs.rs/indexmap/2/indexmap/map/struct.IndexMap.html
+#[cfg(feature = "indexmap")]
+pub type OrderedPreferencesMap<T = String> = indexmap::IndexMap<String, T>;
+
 /// Error type representing the errors that can occur when saving or loading user data.
 #[derive(Debug)]
 pub enum PreferencesError {
@@ -211,6 +340,35 @@ pub enum PreferencesError {
     Io(io::Error),
     /// Couldn't figure out where to put or find the serialized data.
     Directory(AppDirsError),
+    /// Tried to save the data for this key to a read-only backend.
+    ReadOnly(String),
+    /// Timed out waiting for another process to release its lock on the data for this key.
+    WouldBlock(String),
+    /// The data for this key doesn't match its recorded checksum, e.g. because of disk errors.
+    Corrupted(String),
+    /// The data for this key isn't signed, or doesn't match its signature, so it may have been
+    /// tampered with.
+    InvalidSignature(String),
+    /// The data for this key was saved by a newer version of the app or of this crate, with the
+    /// given version header.
+    UnsupportedVersion(String, VersionHeader),
+}
+
+impl PreferencesError {
+    /// Returns `true` if this error means that no user data exists for the requested key.
+    #[must_use]
+    pub fn is_not_found(&self) -> bool {
+        matches!(self, Self::Io(e) if e.kind() == ErrorKind::NotFound)
+    }
+
+    /// Returns `true` if this error means that the saved data itself is damaged.
+    fn is_unreadable_data(&self) -> bool {
+        match self {
+            Self::Json(_) | Self::Corrupted(_) => true,
+            Self::Io(e) => matches!(e.kind(), ErrorKind::InvalidData | ErrorKind::UnexpectedEof),
+            _ => false,
+        }
+    }
 }
 
 impl fmt::Display for PreferencesError {
@@ -220,6 +378,19 @@ impl fmt::Display for PreferencesError {
             Json(ref e) => e.fmt(f),
             Io(ref e) => e.fmt(f),
             Directory(ref e) => e.fmt(f),
+            ReadOnly(ref key) => write!(f, "Preferences key {key:?} is read-only"),
+            WouldBlock(ref key) => {
+                write!(f, "Preferences key {key:?} is locked by another process")
+            }
+            Corrupted(ref key) => write!(f, "Preferences key {key:?} is corrupted"),
+            InvalidSignature(ref key) => {
+                write!(f, "Preferences key {key:?} has an invalid signature")
+            }
+            UnsupportedVersion(ref key, version) => write!(
+                f,
+                "Preferences key {key:?} was saved in a newer version (format {}, schema {})",
+                version.format, version.schema
+            ),
         }
     }
 }
@@ -227,11 +398,16 @@ impl fmt::Display for PreferencesError {
 impl std::error::Error for PreferencesError {
     fn cause(&self) -> Option<&dyn std::error::Error> {
         use PreferencesError::*;
-        Some(match *self {
-            Json(ref e) => e,
-            Io(ref e) => e,
-            Directory(ref e) => e,
-        })
+        match *self {
+            Json(ref e) => Some(e),
+            Io(ref e) => Some(e),
+            Directory(ref e) => Some(e),
+            ReadOnly(_)
+            | WouldBlock(_)
+            | Corrupted(_)
+            | InvalidSignature(_)
+            | UnsupportedVersion(..) => None,
+        }
     }
 }
 
@@ -313,21 +489,207 @@ pub trait Preferences: Sized {
     /// # Errors
     /// If a read or deserialization error occurs.
     fn load_from<R: Read>(reader: &mut R) -> Result<Self, PreferencesError>;
-}
-
-fn compute_file_path<S: AsRef<str>>(app: &AppInfo, key: S) -> Result<PathBuf, PreferencesError> {
-    let mut path = get_app_dir(DATA_TYPE, app, key.as_ref())?;
-    let new_name = match path.file_name() {
-        Some(name) if !name.is_empty() => {
-            let mut new_name = OsString::with_capacity(name.len() + PREFS_FILE_EXTENSION.len());
-            new_name.push(name);
-            new_name.push(PREFS_FILE_EXTENSION);
-            new_name
+    /// Returns the exact bytes that `save` would write for the current state of this object,
+    /// without touching the disk.
+    ///
+    /// This is useful for golden-file tests that catch accidental changes to the saved format:
+    ///
+    /// ```
+    /// use preferences::Preferences;
+    /// use serde::Serialize;
+    ///
+    /// #[derive(Serialize, serde::Deserialize)]
+    /// struct Graphics {
+    ///     fullscreen: bool,
+    /// }
+    ///
+    /// let bytes = Graphics { fullscreen: true }.serialized().unwrap();
+    /// assert_eq!(bytes, br#"{"fullscreen":true}"#);
+    /// ```
+    ///
+    /// # Errors
+    /// If a serialization error occurs.
+    fn serialized(&self) -> Result<Vec<u8>, PreferencesError> {
+        let mut data = Vec::new();
+        self.save_to(&mut data)?;
+        Ok(data)
+    }
+    /// Same as `save`, but writes the serialized preferences to an arbitrary
+    /// [`Backend`](trait.Backend.html).
+    ///
+    /// # Errors
+    /// If a serialization error occurs, or if the backend fails to write the data.
+    fn save_in<B, S>(&self, backend: &B, app: &AppInfo, key: S) -> Result<(), PreferencesError>
+    where
+        B: Backend + ?Sized,
+        S: AsRef<str>,
+    {
+        backend.write(app, key.as_ref(), &self.serialized()?)
+    }
+    /// Same as `load`, but reads the serialized preferences from an arbitrary
+    /// [`Backend`](trait.Backend.html).
+    ///
+    /// # Errors
+    /// If a deserialization error occurs, or if the backend fails to read the data.
+    fn load_in<B, S>(backend: &B, app: &AppInfo, key: S) -> Result<Self, PreferencesError>
+    where
+        B: Backend + ?Sized,
+        S: AsRef<str>,
+    {
+        let data = backend.read(app, key.as_ref())?;
+        Self::load_from(&mut data.as_slice())
+    }
+    /// Loads every key in `keys`, without stopping at the first error.
+    ///
+    /// ```
+    /// use preferences::{AppInfo, Preferences};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// 1.save(&APP_INFO, "tests/docs/bulk/one").unwrap();
+    /// let report = i32::load_many(&APP_INFO, ["tests/docs/bulk/one", "tests/docs/bulk/none"]);
+    /// assert_eq!(report.succeeded["tests/docs/bulk/one"], 1);
+    /// assert!(report.failed[0].1.is_not_found());
+    /// ```
+    fn load_many<I>(app: &AppInfo, keys: I) -> BulkReport<Self>
+    where
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        Self::load_many_in(&FileBackend::new(), app, keys)
+    }
+    /// Same as `load_many`, but reads the serialized preferences from an arbitrary
+    /// [`Backend`](trait.Backend.html).
+    fn load_many_in<B, I>(backend: &B, app: &AppInfo, keys: I) -> BulkReport<Self>
+    where
+        B: Backend + ?Sized,
+        I: IntoIterator,
+        I::Item: AsRef<str>,
+    {
+        let mut report = BulkReport::default();
+        for key in keys {
+            let key = key.as_ref();
+            report.record(key, Self::load_in(backend, app, key));
         }
-        _ => DEFAULT_PREFS_FILENAME.into(),
-    };
-    path.set_file_name(new_name);
-    Ok(path)
+        report
+    }
+    /// Same as `load`, but returns the default value if no user data exists for that `key`.
+    ///
+    /// ```
+    /// use preferences::{AppInfo, Preferences, PreferencesMap};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let prefs = PreferencesMap::<String>::load_or_default(&APP_INFO, "tests/docs/never-saved");
+    /// assert!(prefs.unwrap().is_empty());
+    /// ```
+    ///
+    /// # Errors
+    /// If the data exists but can't be read or deserialized.
+    fn load_or_default<S: AsRef<str>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError>
+    where
+        Self: Default,
+    {
+        match Self::load(app, key) {
+            Err(e) if e.is_not_found() => Ok(Self::default()),
+            result => result,
+        }
+    }
+    /// Same as `load_or_default`, but also saves the default value if no user data exists for
+    /// that `key`, so the file is there to be found (and edited) from then on.
+    ///
+    /// # Errors
+    /// If the data exists but can't be read or deserialized, or if the default value can't be
+    /// saved.
+    fn load_or_save_default<S: AsRef<str>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError>
+    where
+        Self: Default,
+    {
+        Self::load_or_insert_with(app, key, Self::default)
+    }
+    /// Same as `load_or_save_default`, but creates the value with `f` rather than `Default`.
+    ///
+    /// ```
+    /// use preferences::{AppInfo, Preferences, PreferencesMap};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let prefs = PreferencesMap::<u32>::load_or_insert_with(&APP_INFO, "tests/docs/insert", || {
+    ///     let mut prefs = PreferencesMap::new();
+    ///     prefs.insert("volume".into(), 11);
+    ///     prefs
+    /// });
+    /// assert!(prefs.unwrap().contains_key("volume"));
+    /// ```
+    ///
+    /// # Errors
+    /// If the data exists but can't be read or deserialized, or if the new value can't be saved.
+    fn load_or_insert_with<S, F>(app: &AppInfo, key: S, f: F) -> Result<Self, PreferencesError>
+    where
+        S: AsRef<str>,
+        F: FnOnce() -> Self,
+    {
+        let key = key.as_ref();
+        match Self::load(app, key) {
+            Err(e) if e.is_not_found() => {
+                let value = f();
+                value.save(app, key)?;
+                Ok(value)
+            }
+            result => result,
+        }
+    }
+    /// Same as `load`, but falls back to the value returned by `fallback` (and saves it) if no
+    /// user data exists for that `key`, or if the saved data is unreadable or corrupted.
+    ///
+    /// Unreadable data is moved aside rather than overwritten (see
+    /// [`FileBackend::set_aside`](struct.FileBackend.html#method.set_aside)), so it can still be
+    /// inspected or repaired by hand.
+    ///
+    /// ```
+    /// use preferences::{AppInfo, Preferences, PreferencesMap};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let prefs = PreferencesMap::<u32>::load_or_recover(&APP_INFO, "tests/docs/recover", || {
+    ///     let mut prefs = PreferencesMap::new();
+    ///     prefs.insert("volume".into(), 11);
+    ///     prefs
+    /// });
+    /// assert!(prefs.unwrap().contains_key("volume"));
+    /// ```
+    ///
+    /// # Errors
+    /// If the data can't be read for any other reason (e.g. permission denied), or if the
+    /// fallback value can't be saved.
+    fn load_or_recover<S, F>(app: &AppInfo, key: S, fallback: F) -> Result<Self, PreferencesError>
+    where
+        S: AsRef<str>,
+        F: FnOnce() -> Self,
+    {
+        let backend = FileBackend::new();
+        let key = key.as_ref();
+        match Self::load_in(&backend, app, key) {
+            Err(e) if e.is_not_found() => {}
+            Err(e) if e.is_unreadable_data() => {
+                backend.set_aside(app, key)?;
+            }
+            result => return result,
+        }
+        let value = fallback();
+        value.save_in(&backend, app, key)?;
+        Ok(value)
+    }
+    /// Same as `load`, but first upgrades the saved data to the current schema version with
+    /// `migrator` (see [`Migrator::migrate`](struct.Migrator.html#method.migrate)).
+    ///
+    /// # Errors
+    /// If the data can't be migrated, or if a deserialization or file I/O error occurs.
+    fn load_with_migrations<S: AsRef<str>>(
+        migrator: &Migrator,
+        app: &AppInfo,
+        key: S,
+    ) -> Result<Self, PreferencesError> {
+        let key = key.as_ref();
+        migrator.migrate(app, key)?;
+        Self::load_in(&migrator.backend(), app, key)
+    }
 }
 
 impl<T> Preferences for T
@@ -338,15 +700,10 @@ where
     where
         S: AsRef<str>,
     {
-        let path = compute_file_path(app, key.as_ref())?;
-        path.parent().map(create_dir_all);
-        let mut file = File::create(path)?;
-        self.save_to(&mut file)
+        self.save_in(&FileBackend::new(), app, key)
     }
     fn load<S: AsRef<str>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError> {
-        let path = compute_file_path(app, key.as_ref())?;
-        let mut file = File::open(path)?;
-        Self::load_from(&mut file)
+        Self::load_in(&FileBackend::new(), app, key)
     }
     fn save_to<W: Write>(&self, writer: &mut W) -> Result<(), PreferencesError> {
         serde_json::to_writer(writer, self).map_err(Into::into)
@@ -360,15 +717,24 @@ where
 ///
 /// This makes no guarantees that the specified directory path actually *exists* (though you can
 /// easily use `std::fs::create_dir_all(..)`). Returns `None` if the directory cannot be determined
-/// or is not available on the current platform.
+/// or is not available on the current platform. Honours the
+/// [`PREFERENCES_BASE_DIR`](static.BASE_DIR_ENV_VAR.html) environment variable.
 #[must_use]
 pub fn prefs_base_dir() -> Option<PathBuf> {
-    get_data_root(AppDataType::UserConfig).ok()
+    base_dir_override().or_else(|| get_data_root(AppDataType::UserConfig).ok())
 }
 
 #[cfg(test)]
 mod tests {
-    use super::{AppInfo, Preferences, PreferencesMap};
+    use super::{
+        apply_patch_in, merge, merge_maps, merge_patch, AppInfo, AutoSave, Backend, Capabilities,
+        ChangeKind, Consistency, ContentType, CrashMarker, EnvBackend, EnvOverrides, FileBackend,
+        FlushGuard, KeepUnknown, Layer, LayeredBackend, LayeredPreferences, MergeStrategy,
+        Metadata, Migrator, Preferences, PreferencesError, PreferencesMap, PrefsHandle,
+        PrefsLockGuard, PrefsSaver, ProjectBackend, ReadOnly, SaveOptions, Scope, Transaction,
+        TrustLevel, TrustStore,
+    };
+    use app_dirs::{get_data_root, AppDataType};
     const APP_INFO: AppInfo = AppInfo {
         name: "preferences",
         author: "Rust language community",
@@ -402,4 +768,1405 @@ mod tests {
         assert_eq!(load_map_result.unwrap(), sample_map);
         assert_eq!(load_other_result.unwrap(), sample_other);
     }
+    #[test]
+    fn test_serialized() {
+        let sample = vec![(1, "one".to_owned())];
+        let bytes = sample.serialized().unwrap();
+        assert_eq!(bytes, br#"[[1,"one"]]"#);
+        let name = gen_test_name("serialized");
+        sample.save(&APP_INFO, &name).unwrap();
+        assert_eq!(FileBackend::new().read(&APP_INFO, &name).unwrap(), bytes);
+    }
+    #[test]
+    fn test_layered_backend() {
+        let name = gen_test_name("layered-backend");
+        let env = EnvBackend::with_prefix("PREFERENCES_LAYERED");
+        let backend = LayeredBackend::new()
+            .layer(env.clone())
+            .writable_layer(FileBackend::new());
+        5.save_in(&backend, &APP_INFO, &name).unwrap();
+        assert_eq!(i32::load_in(&backend, &APP_INFO, &name).unwrap(), 5);
+        std::env::set_var(env.var_name(&APP_INFO, &name), "7");
+        assert_eq!(i32::load_in(&backend, &APP_INFO, &name).unwrap(), 7);
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 5);
+        let missing = i32::load_in(&backend, &APP_INFO, gen_test_name("layered-missing"));
+        assert!(missing.unwrap_err().is_not_found());
+        assert!(5.save_in(&LayeredBackend::new(), &APP_INFO, &name).is_err());
+    }
+    #[test]
+    fn test_layered_preferences() {
+        let dir = std::env::temp_dir().join("preferences-layered-prefs");
+        let system = FileBackend::with_base_dir(dir.join("system"));
+        let user = FileBackend::with_base_dir(dir.join("user"));
+        let name = gen_test_name("layered-prefs");
+        let mut defaults = PreferencesMap::new();
+        defaults.insert("volume".to_owned(), serde_json::json!(5));
+        defaults.insert(
+            "window".to_owned(),
+            serde_json::json!({"width": 640, "height": 480}),
+        );
+        defaults.save_in(&system, &APP_INFO, &name).unwrap();
+        user.remove(&APP_INFO, &name).unwrap();
+        let load = || {
+            LayeredPreferences::<PreferencesMap<serde_json::Value>>::with_backends(
+                &system,
+                user.clone(),
+                &APP_INFO,
+                &name,
+            )
+        };
+        let mut prefs = load().unwrap();
+        assert_eq!(prefs.source("volume"), Some(Layer::System));
+        assert_eq!(prefs.source("muted"), None);
+        prefs
+            .set("window", serde_json::json!({"width": 800}))
+            .unwrap();
+        prefs.set_override("volume", 0).unwrap();
+        prefs.set("volume", 9).unwrap();
+        assert_eq!(prefs.get()["volume"], 0);
+        assert_eq!(
+            prefs.get()["window"],
+            serde_json::json!({"width": 800, "height": 480})
+        );
+        assert_eq!(prefs.source("window"), Some(Layer::User));
+        prefs.save().unwrap();
+        let mut prefs = load().unwrap();
+        assert_eq!(prefs.get()["volume"], 9);
+        prefs.reset("volume").unwrap();
+        assert_eq!(prefs.get()["volume"], 5);
+        let saved = PreferencesMap::<serde_json::Value>::load_in(&user, &APP_INFO, &name);
+        assert_eq!(saved.unwrap().len(), 2);
+    }
+    #[test]
+    fn test_merge() {
+        use serde_json::json;
+        let base = json!({"a": {"x": 1, "list": [1]}, "b": 2});
+        let other = json!({"a": {"y": 3, "list": [2]}, "b": null, "c": 4});
+        let merged = |strategy| {
+            let mut merged = base.clone();
+            merge(&mut merged, other.clone(), strategy);
+            merged
+        };
+        let replaced = json!({"a": {"y": 3, "list": [2]}, "b": null, "c": 4});
+        assert_eq!(merged(MergeStrategy::Replace), replaced);
+        let deep = json!({"a": {"x": 1, "y": 3, "list": [2]}, "b": null, "c": 4});
+        assert_eq!(merged(MergeStrategy::Deep), deep);
+        let appended = json!({"a": {"x": 1, "y": 3, "list": [1, 2]}, "b": null, "c": 4});
+        assert_eq!(merged(MergeStrategy::AppendArrays), appended);
+        let kept = json!({"a": {"x": 1, "y": 3, "list": [1]}, "b": 2, "c": 4});
+        assert_eq!(merged(MergeStrategy::KeepExisting), kept);
+
+        let mut base = PreferencesMap::new();
+        base.insert("recent".to_owned(), vec![1]);
+        let mut other = PreferencesMap::new();
+        other.insert("recent".to_owned(), vec![2]);
+        other.insert("pinned".to_owned(), vec![3]);
+        merge_maps(&mut base, other, MergeStrategy::AppendArrays).unwrap();
+        assert_eq!(base["recent"], [1, 2]);
+        assert_eq!(base["pinned"], [3]);
+    }
+    #[test]
+    fn test_merge_patch() {
+        use serde_json::json;
+        // Examples from RFC 7396, appendix A.
+        let cases = [
+            (json!({"a": "b"}), json!({"a": "c"}), json!({"a": "c"})),
+            (
+                json!({"a": "b"}),
+                json!({"b": "c"}),
+                json!({"a": "b", "b": "c"}),
+            ),
+            (json!({"a": "b"}), json!({"a": null}), json!({})),
+            (
+                json!({"a": "b", "b": "c"}),
+                json!({"a": null}),
+                json!({"b": "c"}),
+            ),
+            (json!({"a": ["b"]}), json!({"a": "c"}), json!({"a": "c"})),
+            (json!({"a": "c"}), json!({"a": ["b"]}), json!({"a": ["b"]})),
+            (
+                json!({"a": {"b": "c"}}),
+                json!({"a": {"b": "d", "c": null}}),
+                json!({"a": {"b": "d"}}),
+            ),
+            (
+                json!({"a": [{"b": "c"}]}),
+                json!({"a": [1]}),
+                json!({"a": [1]}),
+            ),
+            (json!(["a", "b"]), json!(["c", "d"]), json!(["c", "d"])),
+            (json!({"a": "b"}), json!(["c"]), json!(["c"])),
+            (json!({"a": "foo"}), json!(null), json!(null)),
+            (json!({"a": "foo"}), json!("bar"), json!("bar")),
+            (
+                json!({"e": null}),
+                json!({"a": 1}),
+                json!({"e": null, "a": 1}),
+            ),
+            (
+                json!([1, 2]),
+                json!({"a": "b", "c": null}),
+                json!({"a": "b"}),
+            ),
+            (
+                json!({}),
+                json!({"a": {"bb": {"ccc": null}}}),
+                json!({"a": {"bb": {}}}),
+            ),
+        ];
+        for (mut target, patch, expected) in cases {
+            merge_patch(&mut target, &patch);
+            assert_eq!(target, expected);
+        }
+
+        let backend = FileBackend::with_base_dir(std::env::temp_dir().join("preferences-patch"));
+        let name = gen_test_name("patch");
+        backend.remove(&APP_INFO, &name).unwrap();
+        apply_patch_in(&backend, &APP_INFO, &name, &json!({"a": {"b": 1}})).unwrap();
+        apply_patch_in(&backend, &APP_INFO, &name, &json!({"a": {"c": 2}})).unwrap();
+        let saved = serde_json::Value::load_in(&backend, &APP_INFO, &name).unwrap();
+        assert_eq!(saved, json!({"a": {"b": 1, "c": 2}}));
+    }
+    #[test]
+    fn test_provenance() {
+        let name = gen_test_name("provenance");
+        let plain = FileBackend::new();
+        let watermarked = FileBackend::new().with_provenance("1.2.3");
+        true.save_in(&watermarked, &APP_INFO, &name).unwrap();
+        let provenance = plain
+            .metadata(&APP_INFO, &name)
+            .unwrap()
+            .provenance
+            .unwrap();
+        assert_eq!(provenance.app_version, "1.2.3");
+        assert_eq!(provenance.crate_version, env!("CARGO_PKG_VERSION"));
+        assert!(bool::load(&APP_INFO, &name).unwrap());
+        true.save_in(&plain, &APP_INFO, &name).unwrap();
+        assert_eq!(
+            plain.metadata(&APP_INFO, &name).unwrap(),
+            Metadata::default()
+        );
+    }
+    #[test]
+    fn test_raw() {
+        let backend = FileBackend::new();
+        let name = gen_test_name("raw");
+        let pem = "-----BEGIN CERTIFICATE-----\n...\n-----END CERTIFICATE-----\n";
+        backend
+            .save_raw(&APP_INFO, &name, pem, ContentType::Text)
+            .unwrap();
+        assert_eq!(backend.load_raw(&APP_INFO, &name).unwrap(), pem.as_bytes());
+        let metadata = backend.metadata(&APP_INFO, &name).unwrap();
+        assert_eq!(metadata.content_type, Some(ContentType::Text));
+        backend
+            .save_raw(&APP_INFO, &name, [0, 159, 146, 150], ContentType::Binary)
+            .unwrap();
+        assert_eq!(
+            backend.load_raw(&APP_INFO, &name).unwrap(),
+            [0, 159, 146, 150]
+        );
+        7.save(&APP_INFO, &name).unwrap();
+        assert_eq!(
+            backend.metadata(&APP_INFO, &name).unwrap().content_type,
+            None
+        );
+    }
+    #[test]
+    fn test_locking() {
+        use fs2::FileExt;
+        use std::time::Duration;
+        let backend = FileBackend::new().with_lock_timeout(Duration::from_millis(50));
+        let name = gen_test_name("locking");
+        1.save_in(&backend, &APP_INFO, &name).unwrap();
+        let lock_path = crate::lock::lock_path(&backend.path(&APP_INFO, &name).unwrap());
+        let other = std::fs::File::open(lock_path).unwrap();
+        FileExt::lock_exclusive(&other).unwrap();
+        let result = 2.save_in(&backend, &APP_INFO, &name);
+        assert!(matches!(result, Err(PreferencesError::WouldBlock(key)) if key == name));
+        assert!(i32::load_in(&backend, &APP_INFO, &name).is_err());
+        FileExt::unlock(&other).unwrap();
+        FileExt::lock_shared(&other).unwrap();
+        assert_eq!(i32::load_in(&backend, &APP_INFO, &name).unwrap(), 1);
+    }
+    #[test]
+    fn test_schema_version() {
+        let backend = FileBackend::new().with_schema_version(2);
+        let name = gen_test_name("schema-version");
+        backend.remove(&APP_INFO, &name).unwrap();
+        5.save(&APP_INFO, &name).unwrap();
+        assert_eq!(backend.metadata(&APP_INFO, &name).unwrap().version, None);
+        assert_eq!(i32::load_in(&backend, &APP_INFO, &name).unwrap(), 5);
+        6.save_in(&backend, &APP_INFO, &name).unwrap();
+        let version = backend.metadata(&APP_INFO, &name).unwrap().version.unwrap();
+        assert_eq!((version.format, version.schema), (1, 2));
+        let older = FileBackend::new().with_schema_version(1);
+        let result = i32::load_in(&older, &APP_INFO, &name);
+        assert!(
+            matches!(result, Err(PreferencesError::UnsupportedVersion(key, v)) if key == name && v == version)
+        );
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 6);
+        // A garbled metadata sidecar doesn't keep a plain save from repairing it.
+        let mut meta = backend.path(&APP_INFO, &name).unwrap().into_os_string();
+        meta.push(".meta");
+        std::fs::write(&meta, "{\"version\": ").unwrap();
+        7.save(&APP_INFO, &name).unwrap();
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 7);
+        assert_eq!(backend.metadata(&APP_INFO, &name).unwrap().version, None);
+    }
+    #[test]
+    fn test_migrations() {
+        let backend = FileBackend::with_base_dir(std::env::temp_dir().join("preferences-migrate"));
+        let name = gen_test_name("migrate");
+        vec![1, 2]
+            .save_in(&backend.clone().with_schema_version(2), &APP_INFO, &name)
+            .unwrap();
+        let migrator = Migrator::with_backend(backend.clone())
+            .step(1, |_| unreachable!())
+            .step(2, |value| value.as_array_mut().unwrap().push(3.into()))
+            .step(3, |value| *value = value.as_array().unwrap().len().into());
+        assert_eq!(migrator.version(), 4);
+        let count = usize::load_with_migrations(&migrator, &APP_INFO, &name).unwrap();
+        assert_eq!(count, 3);
+        let version = backend.metadata(&APP_INFO, &name).unwrap().version.unwrap();
+        assert_eq!(version.schema, 4);
+        assert!(!migrator.migrate(&APP_INFO, &name).unwrap());
+        assert!(!migrator.migrate(&APP_INFO, "missing").unwrap());
+        // Saving migrated data, with or without the migrator's version, doesn't migrate it again.
+        5.save_in(&backend, &APP_INFO, &name).unwrap();
+        assert_eq!(
+            usize::load_with_migrations(&migrator, &APP_INFO, &name).unwrap(),
+            5
+        );
+        6.save_in(&migrator.backend(), &APP_INFO, &name).unwrap();
+        assert_eq!(
+            usize::load_with_migrations(&migrator, &APP_INFO, &name).unwrap(),
+            6
+        );
+        assert_eq!(
+            migrator.rollback_migration(&APP_INFO, &name).unwrap(),
+            Some(2)
+        );
+        assert_eq!(
+            Vec::<i32>::load_in(&backend, &APP_INFO, &name).unwrap(),
+            [1, 2]
+        );
+        assert_eq!(migrator.rollback_migration(&APP_INFO, &name).unwrap(), None);
+    }
+    #[test]
+    fn test_keep_unknown() {
+        #[derive(serde::Serialize, serde::Deserialize)]
+        struct Window {
+            width: u32,
+        }
+        let name = gen_test_name("keep-unknown");
+        let sample = gen_sample_prefs();
+        let mut saved: PreferencesMap<serde_json::Value> = PreferencesMap::new();
+        saved.insert("width".into(), 640.into());
+        saved.insert("sample".into(), serde_json::to_value(&sample).unwrap());
+        saved.save(&APP_INFO, &name).unwrap();
+        let mut window = KeepUnknown::<Window>::load(&APP_INFO, &name).unwrap();
+        assert_eq!(window.unknown().len(), 1);
+        window.width = 800;
+        window.save(&APP_INFO, &name).unwrap();
+        saved.insert("width".into(), 800.into());
+        assert_eq!(PreferencesMap::load(&APP_INFO, &name).unwrap(), saved);
+        5.save(&APP_INFO, &name).unwrap();
+        assert_eq!(
+            KeepUnknown::<i32>::load(&APP_INFO, &name)
+                .unwrap()
+                .into_inner(),
+            5
+        );
+    }
+    #[test]
+    fn test_snapshots() {
+        let backend =
+            FileBackend::with_base_dir(std::env::temp_dir().join("preferences-snapshots"));
+        let name = gen_test_name("snapshots");
+        backend.remove(&APP_INFO, "other").unwrap();
+        1.save_in(&backend, &APP_INFO, &name).unwrap();
+        backend.snapshot(&APP_INFO, "one").unwrap();
+        2.save_in(&backend, &APP_INFO, &name).unwrap();
+        3.save_in(&backend, &APP_INFO, "other").unwrap();
+        backend.snapshot(&APP_INFO, "two").unwrap();
+        assert_eq!(backend.snapshots(&APP_INFO).unwrap(), ["one", "two"]);
+        backend.restore_snapshot(&APP_INFO, "one").unwrap();
+        assert_eq!(i32::load_in(&backend, &APP_INFO, &name).unwrap(), 1);
+        assert!(i32::load_in(&backend, &APP_INFO, "other")
+            .unwrap_err()
+            .is_not_found());
+        backend.restore_snapshot(&APP_INFO, "two").unwrap();
+        assert_eq!(i32::load_in(&backend, &APP_INFO, "other").unwrap(), 3);
+        // An empty label names no snapshot, rather than all of them.
+        match backend.remove_snapshot(&APP_INFO, "") {
+            Err(PreferencesError::Io(e)) => assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput),
+            result => panic!("unexpected result: {result:?}"),
+        }
+        assert_eq!(backend.snapshots(&APP_INFO).unwrap(), ["one", "two"]);
+        backend.remove_snapshot(&APP_INFO, "one").unwrap();
+        backend.remove_snapshot(&APP_INFO, "two").unwrap();
+        let result = backend.restore_snapshot(&APP_INFO, "one");
+        assert!(result.unwrap_err().is_not_found());
+    }
+    #[test]
+    fn test_checksums() {
+        let backend = FileBackend::new().with_checksums();
+        let name = gen_test_name("checksums");
+        let hello = String::from("hello");
+        hello.save_in(&backend, &APP_INFO, &name).unwrap();
+        let metadata = backend.metadata(&APP_INFO, &name).unwrap();
+        assert!(metadata.checksum.is_some());
+        let path = backend.path(&APP_INFO, &name).unwrap();
+        std::fs::write(&path, "\"jello\"").unwrap();
+        // Rewrite the metadata afterwards, so the change doesn't look like a hand edit.
+        let metadata_path = format!("{}.meta", path.display());
+        std::fs::write(&metadata_path, std::fs::read(&metadata_path).unwrap()).unwrap();
+        let result = String::load_in(&backend, &APP_INFO, &name);
+        assert!(matches!(result, Err(PreferencesError::Corrupted(key)) if key == name));
+        assert_eq!(String::load(&APP_INFO, &name).unwrap(), "jello");
+    }
+    #[test]
+    #[cfg(feature = "signing")]
+    fn test_signing() {
+        let backend = FileBackend::new().with_signing_key("secret");
+        let name = gen_test_name("signing");
+        let other = gen_test_name("signing-other");
+        100.save_in(&backend, &APP_INFO, &name).unwrap();
+        200.save_in(&backend, &APP_INFO, &other).unwrap();
+        assert_eq!(i32::load_in(&backend, &APP_INFO, &name).unwrap(), 100);
+        let invalid =
+            |result| matches!(result, Err(PreferencesError::InvalidSignature(key)) if key == name);
+        let forged = FileBackend::new().with_signing_key("guess");
+        assert!(invalid(i32::load_in(&forged, &APP_INFO, &name)));
+        let path = backend.path(&APP_INFO, &name).unwrap();
+        std::fs::write(&path, "999999").unwrap();
+        assert!(invalid(i32::load_in(&backend, &APP_INFO, &name)));
+        // Someone else's signature doesn't help either.
+        let other_path = backend.path(&APP_INFO, &other).unwrap();
+        std::fs::copy(&other_path, &path).unwrap();
+        let metadata_path = |path: &std::path::Path| format!("{}.meta", path.display());
+        std::fs::copy(metadata_path(&other_path), metadata_path(&path)).unwrap();
+        assert!(invalid(i32::load_in(&backend, &APP_INFO, &name)));
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 200);
+    }
+    #[test]
+    fn test_load_or_default() {
+        let name = gen_test_name("load-or-default");
+        let path = FileBackend::new().path(&APP_INFO, &name).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(u8::load_or_default(&APP_INFO, &name).unwrap(), 0);
+        assert!(!path.exists());
+        assert_eq!(u8::load_or_save_default(&APP_INFO, &name).unwrap(), 0);
+        assert_eq!(u8::load(&APP_INFO, &name).unwrap(), 0);
+        7.save(&APP_INFO, &name).unwrap();
+        assert_eq!(u8::load_or_default(&APP_INFO, &name).unwrap(), 7);
+        assert_eq!(u8::load_or_save_default(&APP_INFO, &name).unwrap(), 7);
+        String::from("seven").save(&APP_INFO, &name).unwrap();
+        assert!(u8::load_or_default(&APP_INFO, &name).is_err());
+    }
+    #[test]
+    fn test_load_or_recover() {
+        let name = gen_test_name("load-or-recover");
+        let path = FileBackend::new().path(&APP_INFO, &name).unwrap();
+        std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+        std::fs::write(&path, "{ not json").unwrap();
+        let value = i32::load_or_recover(&APP_INFO, &name, || 7).unwrap();
+        assert_eq!(value, 7);
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 7);
+        let aside = format!("{}.corrupt", path.display());
+        assert_eq!(std::fs::read_to_string(aside).unwrap(), "{ not json");
+        let value = i32::load_or_recover(&APP_INFO, &name, || unreachable!()).unwrap();
+        assert_eq!(value, 7);
+    }
+    #[test]
+    fn test_load_many() {
+        let backend = EnvBackend::with_prefix("PREFERENCES_BULK");
+        std::env::set_var("PREFERENCES_BULK_GOOD", "1");
+        std::env::set_var("PREFERENCES_BULK_BAD", "[]");
+        let report = i32::load_many_in(&backend, &APP_INFO, ["good", "bad", "missing"]);
+        assert!(!report.is_ok());
+        assert_eq!(report.succeeded.len(), 1);
+        assert_eq!(report.succeeded["good"], 1);
+        let failed: Vec<_> = report.failed.iter().map(|(key, _)| key.as_str()).collect();
+        assert_eq!(failed, ["bad", "missing"]);
+        assert!(report.failed[1].1.is_not_found());
+        assert!(report.into_result().is_err());
+    }
+    #[test]
+    fn test_lock_guard() {
+        let backend =
+            FileBackend::with_base_dir(std::env::temp_dir().join("preferences-lock-test"));
+        let timeout = std::time::Duration::from_millis(20);
+        let guard = PrefsLockGuard::acquire_in(&backend, &APP_INFO, timeout).unwrap();
+        let result = PrefsLockGuard::acquire_in(&backend, &APP_INFO, timeout);
+        assert!(matches!(result, Err(PreferencesError::WouldBlock(_))));
+        drop(guard);
+        PrefsLockGuard::acquire_in(&backend, &APP_INFO, timeout).unwrap();
+    }
+    #[test]
+    fn test_read_only() {
+        let name = gen_test_name("read-only");
+        String::from("admin").save(&APP_INFO, &name).unwrap();
+        let backend = ReadOnly::new(FileBackend::new());
+        assert_eq!(
+            String::load_in(&backend, &APP_INFO, &name).unwrap(),
+            "admin"
+        );
+        let result = String::from("user").save_in(&backend, &APP_INFO, &name);
+        assert!(matches!(result, Err(PreferencesError::ReadOnly(key)) if key == name));
+        assert_eq!(String::load(&APP_INFO, &name).unwrap(), "admin");
+    }
+    #[test]
+    fn test_base_dir() {
+        let dir = std::env::temp_dir().join("preferences-base-dir-test");
+        let backend = FileBackend::with_base_dir(&dir);
+        let path = backend.path(&APP_INFO, "options/graphics").unwrap();
+        let expected = dir.join("preferences/options/graphics.prefs.json");
+        assert_eq!(path, expected);
+        let name = gen_test_name("base-dir");
+        "custom"
+            .to_owned()
+            .save_in(&backend, &APP_INFO, &name)
+            .unwrap();
+        assert_eq!(
+            String::load_in(&backend, &APP_INFO, &name).unwrap(),
+            "custom"
+        );
+        assert!(backend.path(&APP_INFO, &name).unwrap().starts_with(&dir));
+    }
+    #[test]
+    fn test_base_dir_env_var() {
+        use super::BASE_DIR_ENV_VAR;
+        let dir = std::env::temp_dir().join("preferences-base-dir-env-test");
+        // Setting the variable here would move the files of the tests running alongside, so
+        // this test runs itself again in a child process with the variable set.
+        if std::env::var_os(BASE_DIR_ENV_VAR).is_none() {
+            let _ = std::fs::remove_dir_all(&dir);
+            let status = std::process::Command::new(std::env::current_exe().unwrap())
+                .args(["--exact", "tests::test_base_dir_env_var"])
+                .env(BASE_DIR_ENV_VAR, &dir)
+                .status()
+                .unwrap();
+            assert!(status.success());
+            let backend = FileBackend::with_base_dir(&dir);
+            let name = gen_test_name("base-dir-env");
+            assert_eq!(String::load_in(&backend, &APP_INFO, name).unwrap(), "moved");
+            return;
+        }
+        let name = gen_test_name("base-dir-env");
+        "moved".to_owned().save(&APP_INFO, &name).unwrap();
+        assert!(FileBackend::new()
+            .path(&APP_INFO, &name)
+            .unwrap()
+            .starts_with(&dir));
+        assert_eq!(super::prefs_base_dir().unwrap(), dir);
+        let system = FileBackend::with_scope(Scope::System).path(&APP_INFO, &name);
+        assert!(system.map_or(true, |path| !path.starts_with(&dir)));
+    }
+    #[test]
+    fn test_scope() {
+        let key = "options/graphics";
+        let user = FileBackend::with_scope(Scope::User).path(&APP_INFO, key);
+        assert_eq!(
+            user.unwrap(),
+            FileBackend::new().path(&APP_INFO, key).unwrap()
+        );
+        if let Ok(root) = get_data_root(AppDataType::SharedConfig) {
+            let system = FileBackend::with_scope(Scope::System).path(&APP_INFO, key);
+            assert!(system.unwrap().starts_with(root));
+        }
+    }
+    #[test]
+    fn test_portable() {
+        let backend = FileBackend::portable_in("portable-prefs").unwrap();
+        let name = gen_test_name("portable");
+        let path = backend.path(&APP_INFO, &name).unwrap();
+        let exe = std::env::current_exe().unwrap();
+        assert!(path.starts_with(exe.parent().unwrap().join("portable-prefs")));
+        3.save_in(&backend, &APP_INFO, &name).unwrap();
+        assert!(path.is_file());
+        assert_eq!(i32::load_in(&backend, &APP_INFO, &name).unwrap(), 3);
+    }
+    #[test]
+    fn test_capabilities() {
+        struct Atomic;
+        impl Backend for Atomic {
+            fn read(&self, _: &AppInfo, _: &str) -> Result<Vec<u8>, PreferencesError> {
+                Ok(b"null".to_vec())
+            }
+            fn write(&self, _: &AppInfo, _: &str, _: &[u8]) -> Result<(), PreferencesError> {
+                Ok(())
+            }
+            fn capabilities(&self) -> Capabilities {
+                Capabilities {
+                    atomic: true,
+                    ..Capabilities::default()
+                }
+            }
+        }
+        let atomic = Atomic.capabilities();
+        assert_eq!(
+            ReadOnly::new(Atomic).capabilities(),
+            Capabilities::default()
+        );
+        let read_only = ReadOnly::new(FileBackend::new()).capabilities();
+        assert!(!read_only.atomic && !read_only.transactional);
+        assert_eq!(read_only.consistency, Consistency::Unspecified);
+        let layered = LayeredBackend::new().layer(EnvBackend::new());
+        assert_eq!(layered.writable_layer(Atomic).capabilities(), atomic);
+        assert_eq!(EnvBackend::new().capabilities(), Capabilities::default());
+        let file = FileBackend::new().capabilities();
+        assert!(file.atomic && file.transactional);
+        assert_eq!(file.consistency, Consistency::Atomic);
+        let synced = FileBackend::new().with_save_options(SaveOptions { sync: true });
+        assert_eq!(synced.capabilities().consistency, Consistency::Durable);
+        let name = gen_test_name("synced");
+        5.save_in(&synced, &APP_INFO, &name).unwrap();
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 5);
+    }
+    #[test]
+    fn test_backups() {
+        let backend = FileBackend::new().with_backups(2);
+        let name = gen_test_name("backups");
+        for i in 1..=3 {
+            i.save_in(&backend, &APP_INFO, &name).unwrap();
+        }
+        let history = backend.history(&APP_INFO, &name).unwrap();
+        let numbers: Vec<_> = history.iter().map(|revision| revision.number).collect();
+        assert_eq!(numbers, [1, 2]);
+        assert_eq!(backend.load_version::<i32>(&APP_INFO, &name, 0).unwrap(), 3);
+        assert_eq!(backend.load_version::<i32>(&APP_INFO, &name, 2).unwrap(), 1);
+        let result = backend.load_version::<i32>(&APP_INFO, &name, 3);
+        assert!(result.unwrap_err().is_not_found());
+        backend.restore_backup(&APP_INFO, &name).unwrap();
+        assert_eq!(i32::load_in(&backend, &APP_INFO, &name).unwrap(), 2);
+        backend.restore_backup(&APP_INFO, &name).unwrap();
+        assert_eq!(i32::load_in(&backend, &APP_INFO, &name).unwrap(), 1);
+        let result = backend.restore_backup(&APP_INFO, &name);
+        assert!(result.unwrap_err().is_not_found());
+        assert_eq!(i32::load_in(&backend, &APP_INFO, &name).unwrap(), 1);
+    }
+    #[test]
+    fn test_restore_backup_keeps_version() {
+        let dir = std::env::temp_dir().join("preferences-backup-version-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let backend = FileBackend::with_base_dir(&dir).with_backups(2);
+        vec![1, 2].save_in(&backend, &APP_INFO, "a").unwrap();
+        let migrator = Migrator::with_backend(backend.clone())
+            .step(1, |value| value.as_array_mut().unwrap().push(3.into()))
+            .step(2, |value| *value = value.as_array().unwrap().len().into());
+        assert_eq!(
+            usize::load_with_migrations(&migrator, &APP_INFO, "a").unwrap(),
+            3
+        );
+        4.save_in(&backend, &APP_INFO, "a").unwrap();
+        // Back to the migrated data, which isn't migrated again...
+        backend.restore_backup(&APP_INFO, "a").unwrap();
+        assert_eq!(
+            usize::load_with_migrations(&migrator, &APP_INFO, "a").unwrap(),
+            3
+        );
+        // ...then to the data from before the migration, which is.
+        backend.restore_backup(&APP_INFO, "a").unwrap();
+        assert_eq!(
+            usize::load_with_migrations(&migrator, &APP_INFO, "a").unwrap(),
+            3
+        );
+    }
+    #[test]
+    fn test_journaled_writes() {
+        let dir = std::env::temp_dir().join("preferences-journal-test");
+        let backend = FileBackend::with_base_dir(&dir);
+        let writes: [(&str, &[u8]); 2] = [("a", b"1"), ("b", b"2")];
+        backend.write_journaled(&APP_INFO, &writes).unwrap();
+        assert_eq!(i32::load_in(&backend, &APP_INFO, "a").unwrap(), 1);
+        assert_eq!(i32::load_in(&backend, &APP_INFO, "b").unwrap(), 2);
+        // Simulate a crash right after the journal was written.
+        let journal = dir.join("preferences.journal");
+        let entries = r#"[
+            {"key":"a","data":[51],"previous":[49]},
+            {"key":"b","data":[52],"previous":[50]}
+        ]"#;
+        std::fs::write(&journal, entries).unwrap();
+        assert!(backend.recover(&APP_INFO).unwrap());
+        assert!(!journal.exists());
+        assert_eq!(i32::load_in(&backend, &APP_INFO, "a").unwrap(), 3);
+        assert_eq!(i32::load_in(&backend, &APP_INFO, "b").unwrap(), 4);
+        assert!(!backend.recover(&APP_INFO).unwrap());
+    }
+    #[test]
+    fn test_journaled_writes_roll_back() {
+        let dir = std::env::temp_dir().join("preferences-journal-rollback-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let backend = FileBackend::with_base_dir(&dir)
+            .with_lock_timeout(std::time::Duration::from_millis(10));
+        1.save_in(&backend, &APP_INFO, "a").unwrap();
+        // Writing "b" times out while another handle holds its lock.
+        let path = backend.path(&APP_INFO, "b").unwrap();
+        let (_, lock) = backend.hold_lock(&path, "b").unwrap();
+        let writes: [(&str, &[u8]); 2] = [("a", b"5"), ("b", b"6")];
+        let result = backend.write_journaled(&APP_INFO, &writes);
+        assert!(matches!(result, Err(PreferencesError::WouldBlock(_))));
+        assert_eq!(i32::load_in(&backend, &APP_INFO, "a").unwrap(), 1);
+        let journal = dir.join("preferences.journal");
+        assert!(!journal.exists());
+        assert!(dir.join("preferences.journal.failed").exists());
+        drop(lock);
+        assert!(!backend.recover(&APP_INFO).unwrap());
+        backend.write_journaled(&APP_INFO, &writes).unwrap();
+        assert_eq!(i32::load_in(&backend, &APP_INFO, "b").unwrap(), 6);
+        // A journal that can't be parsed is set aside too.
+        std::fs::write(&journal, "[{").unwrap();
+        assert!(backend.recover(&APP_INFO).is_err());
+        assert!(!backend.recover(&APP_INFO).unwrap());
+    }
+    #[test]
+    fn test_transaction() {
+        let backend = FileBackend::with_base_dir(std::env::temp_dir().join("preferences-tx-test"));
+        1.save_in(&backend, &APP_INFO, "c").unwrap();
+        let mut transaction = Transaction::with_backend(backend.clone(), &APP_INFO);
+        transaction.save(&1, "a").unwrap().save(&2, "b").unwrap();
+        transaction.delete("c").save(&3, "a").unwrap();
+        drop(transaction);
+        assert!(i32::load_in(&backend, &APP_INFO, "c").is_ok());
+        let mut transaction = Transaction::with_backend(backend.clone(), &APP_INFO);
+        transaction.save(&1, "a").unwrap().save(&2, "b").unwrap();
+        transaction.delete("c").save(&3, "a").unwrap();
+        transaction.commit().unwrap();
+        assert_eq!(i32::load_in(&backend, &APP_INFO, "a").unwrap(), 3);
+        assert_eq!(i32::load_in(&backend, &APP_INFO, "b").unwrap(), 2);
+        let result = i32::load_in(&backend, &APP_INFO, "c");
+        assert!(result.unwrap_err().is_not_found());
+    }
+    #[test]
+    fn test_event_log() {
+        let dir = std::env::temp_dir().join("preferences-events-test");
+        let _ = std::fs::remove_dir_all(&dir);
+        let backend = FileBackend::with_base_dir(&dir)
+            .with_event_log()
+            .with_provenance("1.2.3");
+        1.save_in(&backend, &APP_INFO, "a").unwrap();
+        22.save_in(&backend, &APP_INFO, "b").unwrap();
+        backend.remove(&APP_INFO, "a").unwrap();
+        let events = backend.recent_events(&APP_INFO, 2).unwrap();
+        assert_eq!(events.len(), 2);
+        assert_eq!(
+            (events[0].key.as_str(), events[0].kind),
+            ("b", ChangeKind::Save)
+        );
+        assert_eq!(events[0].size, 2);
+        assert_eq!(events[0].app_version.as_deref(), Some("1.2.3"));
+        assert_eq!(events[0].process_id, std::process::id());
+        assert_eq!(
+            (events[1].key.as_str(), events[1].kind),
+            ("a", ChangeKind::Delete)
+        );
+        assert_eq!(events[1].checksum, None);
+        assert_eq!(backend.recent_events(&APP_INFO, 10).unwrap().len(), 3);
+    }
+    #[test]
+    fn test_atomic_writes() {
+        let name = gen_test_name("atomic-writes");
+        let small = vec![0_u8; 1];
+        let large = vec![u8::MAX; 1 << 16];
+        small.save(&APP_INFO, &name).unwrap();
+        let writer = {
+            let (name, small, large) = (name.clone(), small.clone(), large.clone());
+            std::thread::spawn(move || {
+                for i in 0..50 {
+                    let value = if i % 2 == 0 { &large } else { &small };
+                    value.save(&APP_INFO, &name).unwrap();
+                }
+            })
+        };
+        while !writer.is_finished() {
+            let loaded = Vec::<u8>::load(&APP_INFO, &name).unwrap();
+            assert!(loaded == small || loaded == large);
+        }
+        writer.join().unwrap();
+    }
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_save_load_async() {
+        use crate::AsyncPreferences;
+        futures_lite::future::block_on(async {
+            let sample = gen_sample_prefs();
+            let name = gen_test_name("save-load-async");
+            sample.save_async(&APP_INFO, &name).await.unwrap();
+            let loaded = PreferencesMap::<String>::load_async(&APP_INFO, &name).await;
+            assert_eq!(loaded.unwrap(), sample);
+            let mut buffer = Vec::new();
+            sample.save_to_async(&mut buffer).await.unwrap();
+            let loaded = PreferencesMap::<String>::load_from_async(&mut buffer.as_slice()).await;
+            assert_eq!(loaded.unwrap(), sample);
+        });
+    }
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_async_backend_adapters() {
+        use crate::{AsyncPreferences, BlockOn, Unblock};
+        let name = gen_test_name("async-backend-adapters");
+        let backend = BlockOn::new(Unblock::new(FileBackend::new()));
+        let sample = gen_sample_prefs();
+        sample.save_in(&backend, &APP_INFO, &name).unwrap();
+        assert_eq!(
+            PreferencesMap::<String>::load(&APP_INFO, &name).unwrap(),
+            sample
+        );
+        let loaded = futures_lite::future::block_on(PreferencesMap::<String>::load_in_async(
+            &backend.into_inner(),
+            &APP_INFO,
+            &name,
+        ));
+        assert_eq!(loaded.unwrap(), sample);
+    }
+    #[cfg(feature = "async")]
+    #[test]
+    fn test_change_stream() {
+        use crate::ChangeStream;
+        use futures_lite::StreamExt;
+        use std::time::Duration;
+        let name = gen_test_name("change-stream");
+        1.save(&APP_INFO, &name).unwrap();
+        let interval = Duration::from_millis(10);
+        let mut changes = ChangeStream::<i32>::with_interval(&APP_INFO, &name, interval).unwrap();
+        std::thread::sleep(interval * 5);
+        1234.save(&APP_INFO, &name).unwrap();
+        let change = futures_lite::future::block_on(changes.next());
+        assert_eq!(change.unwrap().unwrap(), 1234);
+        // A change that keeps the size of the file is noticed too.
+        4321.save(&APP_INFO, &name).unwrap();
+        let change = futures_lite::future::block_on(changes.next());
+        assert_eq!(change.unwrap().unwrap(), 4321);
+        let backend = FileBackend::with_base_dir(std::env::temp_dir().join("preferences-changes"));
+        let mut changes =
+            ChangeStream::<i32>::with_backend(&backend, &APP_INFO, &name, interval).unwrap();
+        std::thread::sleep(interval * 5);
+        5678.save_in(&backend, &APP_INFO, &name).unwrap();
+        let change = futures_lite::future::block_on(changes.next());
+        assert_eq!(change.unwrap().unwrap(), 5678);
+    }
+    #[test]
+    fn test_project_backend() {
+        let root = std::env::temp_dir().join("preferences-project-test");
+        let nested = root.join("src/nested");
+        std::fs::create_dir_all(&nested).unwrap();
+        std::fs::create_dir_all(root.join(".prefs-test")).unwrap();
+        let project = ProjectBackend::discover_from(&nested, ".prefs-test").unwrap();
+        assert_eq!(project.dir(), root.join(".prefs-test"));
+        assert_eq!(
+            project.path("settings"),
+            root.join(".prefs-test/settings.json")
+        );
+        assert_eq!(project.path(""), root.join(".prefs-test/prefs.json"));
+        assert_eq!(project.path("/"), project.path(""));
+        let name = gen_test_name("project-backend");
+        "project"
+            .to_owned()
+            .save_in(&project, &APP_INFO, &name)
+            .unwrap();
+        "user".to_owned().save(&APP_INFO, &name).unwrap();
+        let trusted = LayeredBackend::new()
+            .layer(project.clone())
+            .writable_layer(FileBackend::new());
+        assert_eq!(
+            String::load_in(&trusted, &APP_INFO, &name).unwrap(),
+            "project"
+        );
+        let untrusted = LayeredBackend::new()
+            .layer(project.with_trust_check(|_| false))
+            .writable_layer(FileBackend::new());
+        assert_eq!(
+            String::load_in(&untrusted, &APP_INFO, &name).unwrap(),
+            "user"
+        );
+    }
+    #[test]
+    fn test_trust_store() {
+        let name = gen_test_name("trust-store");
+        let dir = std::env::temp_dir();
+        let mut store = TrustStore::load_key(&APP_INFO, &name).unwrap();
+        store.set_trust(&dir, TrustLevel::Untrusted).unwrap();
+        let mut store = TrustStore::load_key(&APP_INFO, &name).unwrap();
+        assert_eq!(store.trust_level(&dir), Some(TrustLevel::Untrusted));
+        assert!(!store.is_trusted(dir.join(".")));
+        store.set_trust(&dir, TrustLevel::Trusted).unwrap();
+        assert!(TrustStore::load_key(&APP_INFO, &name)
+            .unwrap()
+            .is_trusted(&dir));
+        store.forget(&dir).unwrap();
+        let store = TrustStore::load_key(&APP_INFO, &name).unwrap();
+        assert_eq!(store.trust_level(&dir), None);
+    }
+    #[test]
+    fn test_saver() {
+        let saver = PrefsSaver::new();
+        let name_a = gen_test_name("saver-a");
+        let name_b = gen_test_name("saver-b");
+        for i in 0..50 {
+            saver.save(&i, &APP_INFO, &name_a).unwrap();
+            saver.save(&-i, &APP_INFO, &name_b).unwrap();
+        }
+        saver.flush().unwrap();
+        assert_eq!(i32::load(&APP_INFO, &name_a).unwrap(), 49);
+        assert_eq!(i32::load(&APP_INFO, &name_b).unwrap(), -49);
+        let read_only = PrefsSaver::with_backend(ReadOnly::new(FileBackend::new()));
+        read_only.save(&0, &APP_INFO, &name_a).unwrap();
+        assert!(read_only.flush().is_err());
+        assert!(read_only.flush().is_ok());
+        saver.save(&100, &APP_INFO, &name_a).unwrap();
+        drop(saver);
+        assert_eq!(i32::load(&APP_INFO, &name_a).unwrap(), 100);
+    }
+    #[test]
+    fn test_autosave() {
+        use std::sync::atomic::{AtomicUsize, Ordering};
+        use std::sync::Arc;
+        use std::time::Duration;
+        struct Counting(Arc<AtomicUsize>);
+        impl Backend for Counting {
+            fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+                FileBackend::new().read(app, key)
+            }
+            fn write(&self, app: &AppInfo, key: &str, data: &[u8]) -> Result<(), PreferencesError> {
+                self.0.fetch_add(1, Ordering::SeqCst);
+                FileBackend::new().write(app, key, data)
+            }
+        }
+        let name = gen_test_name("autosave");
+        let writes = Arc::new(AtomicUsize::new(0));
+        let backend = Counting(Arc::clone(&writes));
+        let interval = Duration::from_millis(50);
+        let counter = AutoSave::with_backend(0, backend, &APP_INFO, &name, interval);
+        for _ in 0..1000 {
+            *counter.lock() += 1;
+        }
+        std::thread::sleep(interval * 4);
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 1000);
+        *counter.lock() += 1;
+        assert_eq!(*counter.lock(), 1001);
+        drop(counter);
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 1001);
+        assert!(writes.load(Ordering::SeqCst) < 10);
+    }
+    #[cfg(feature = "indexmap")]
+    #[test]
+    fn test_ordered_map() {
+        use crate::OrderedPreferencesMap;
+        let mut map = OrderedPreferencesMap::new();
+        for key in ["zebra", "apple", "mango", "kiwi"] {
+            map.insert(key.to_owned(), key.len());
+        }
+        let bytes = map.serialized().unwrap();
+        assert_eq!(bytes, br#"{"zebra":5,"apple":5,"mango":5,"kiwi":4}"#);
+        let name = gen_test_name("ordered-map");
+        map.save(&APP_INFO, &name).unwrap();
+        let loaded = OrderedPreferencesMap::<usize>::load(&APP_INFO, &name).unwrap();
+        assert!(loaded.keys().eq(map.keys()));
+    }
+    #[test]
+    fn test_crash_marker() {
+        let name = gen_test_name("crash-marker");
+        CrashMarker::arm_key(&APP_INFO, &name)
+            .unwrap()
+            .disarm()
+            .unwrap();
+        let marker = CrashMarker::arm_key(&APP_INFO, &name).unwrap();
+        assert!(!marker.was_unclean_shutdown());
+        assert!(marker.previous_disarmed_at().is_some());
+        drop(marker);
+        let marker = CrashMarker::arm_key(&APP_INFO, &name).unwrap();
+        assert!(marker.was_unclean_shutdown());
+        assert!(marker.previous_disarmed_at().is_none());
+        assert!(marker.previous_armed_at().unwrap() <= marker.armed_at());
+        marker.disarm().unwrap();
+    }
+    #[test]
+    fn test_flush_guard() {
+        use std::sync::Arc;
+        use std::time::Duration;
+        let name = gen_test_name("flush-guard");
+        let autosave = Arc::new(AutoSave::new(0, &APP_INFO, &name, Duration::from_secs(60)));
+        let mut guard = FlushGuard::new();
+        guard.register(&autosave);
+        *autosave.lock() = 1;
+        autosave.flush().unwrap();
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 1);
+        // Still within the interval, but flushing doesn't wait for it.
+        *autosave.lock() = 2;
+        guard.flush().unwrap();
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 2);
+        *autosave.lock() = 3;
+        let leaked = Arc::clone(&autosave);
+        drop(autosave);
+        drop(guard);
+        assert_eq!(i32::load(&APP_INFO, &name).unwrap(), 3);
+        drop(leaked);
+    }
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_watch() {
+        use std::sync::mpsc::channel;
+        use std::time::Duration;
+        let backend = FileBackend::with_base_dir(std::env::temp_dir().join("preferences-watch"));
+        let name = gen_test_name("watch");
+        let (sender, receiver) = channel();
+        let watcher = crate::PrefsWatcher::with_backend(&backend, &APP_INFO, &name, move |v| {
+            let _ = sender.send(v.map_err(|e| e.to_string()));
+        })
+        .unwrap();
+        assert_eq!(watcher.path(), backend.path(&APP_INFO, &name).unwrap());
+        1.save_in(&backend, &APP_INFO, &name).unwrap();
+        let timeout = Duration::from_secs(10);
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), Ok(1));
+        std::fs::write(watcher.path(), "2").unwrap();
+        assert_eq!(receiver.recv_timeout(timeout).unwrap(), Ok(2));
+        drop(watcher);
+        3.save_in(&backend, &APP_INFO, &name).unwrap();
+        assert!(receiver.recv_timeout(Duration::from_millis(200)).is_err());
+    }
+    #[test]
+    fn test_prefs_handle() {
+        let handle = PrefsHandle::new(&APP_INFO);
+        let volume = gen_test_name("handle/volume");
+        let theme = gen_test_name("handle/theme");
+        let volumes = handle.subscribe::<i32, _>(&volume);
+        let themes = handle.subscribe::<String, _>(&theme);
+        let mismatched = handle.subscribe::<String, _>(&volume);
+        let (other, key) = (handle.clone(), volume.clone());
+        std::thread::spawn(move || other.save(&7, key).unwrap())
+            .join()
+            .unwrap();
+        assert_eq!(volumes.recv().unwrap().unwrap(), 7);
+        assert!(mismatched.recv().unwrap().is_err());
+        assert!(themes.try_recv().is_err());
+        assert_eq!(handle.load::<i32, _>(&volume).unwrap(), 7);
+        drop(volumes);
+        handle.save(&8, &volume).unwrap();
+        assert!(mismatched.recv().unwrap().is_err());
+    }
+    #[cfg(feature = "watch")]
+    #[test]
+    fn test_reloading() {
+        use std::time::{Duration, Instant};
+        let backend = FileBackend::with_base_dir(std::env::temp_dir().join("preferences-reload"));
+        let name = gen_test_name("reloading");
+        1.save_in(&backend, &APP_INFO, &name).unwrap();
+        let reloading = crate::Reloading::<i32>::with_backend(&backend, &APP_INFO, &name).unwrap();
+        let snapshot = reloading.get();
+        assert_eq!(*snapshot, 1);
+        2.save_in(&backend, &APP_INFO, &name).unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while *reloading.get() != 2 {
+            assert!(Instant::now() < deadline, "change wasn't reloaded");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*snapshot, 1);
+        let path = backend.path(&APP_INFO, &name).unwrap();
+        std::fs::write(path, "oops").unwrap();
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while reloading.take_error().is_none() {
+            assert!(Instant::now() < deadline, "error wasn't reported");
+            std::thread::sleep(Duration::from_millis(10));
+        }
+        assert_eq!(*reloading.get(), 2);
+    }
+    #[cfg(all(feature = "watch", unix))]
+    #[test]
+    fn test_change_notifier() {
+        use crate::{ChangeListener, ChangeNotifier};
+        use std::os::unix::fs::PermissionsExt;
+        use std::time::Duration;
+        let app = AppInfo {
+            name: "preferences-notify-test",
+            author: APP_INFO.author,
+        };
+        let notifier = ChangeNotifier::new(&app);
+        let first = ChangeListener::bind(&app).unwrap();
+        let second = ChangeListener::bind(&app).unwrap();
+        assert_eq!(notifier.notify("options/graphics").unwrap(), 2);
+        assert_eq!(first.recv().unwrap(), "options/graphics");
+        assert_eq!(second.recv().unwrap(), "options/graphics");
+        let timeout = Duration::from_millis(10);
+        assert_eq!(first.recv_timeout(timeout).unwrap(), None);
+        let path = second.path().to_owned();
+        drop(second);
+        assert!(!path.exists());
+        assert_eq!(notifier.notify("volume").unwrap(), 1);
+        assert_eq!(first.recv_timeout(timeout).unwrap().unwrap(), "volume");
+        // Refuse a channel directory that other users can access.
+        let dir = first.path().parent().unwrap().to_owned();
+        let mode = std::fs::metadata(&dir).unwrap().permissions().mode();
+        assert_eq!(mode & 0o777, 0o700);
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let bound = ChangeListener::bind(&app);
+        let sent = notifier.notify("volume");
+        std::fs::set_permissions(&dir, std::fs::Permissions::from_mode(0o700)).unwrap();
+        let denied = std::io::ErrorKind::PermissionDenied;
+        assert!(matches!(bound, Err(PreferencesError::Io(ref e)) if e.kind() == denied));
+        assert!(sent.is_err());
+    }
+    #[test]
+    fn test_env_backend() {
+        let backend = EnvBackend::new();
+        let name = gen_test_name("env-backend");
+        let var = backend.var_name(&APP_INFO, &name);
+        assert_eq!(var, "PREFERENCES_TESTS_MODULE_ENV_BACKEND");
+        std::env::set_var(&var, "[1, 2, 3]");
+        assert_eq!(
+            Vec::<u8>::load_in(&backend, &APP_INFO, &name).unwrap(),
+            [1, 2, 3]
+        );
+        std::env::set_var(&var, "not json");
+        assert_eq!(
+            String::load_in(&backend, &APP_INFO, &name).unwrap(),
+            "not json"
+        );
+        assert!(String::from("x")
+            .save_in(&backend, &APP_INFO, &name)
+            .is_err());
+    }
+    #[test]
+    #[cfg(feature = "clap")]
+    fn test_arg_overrides() {
+        use clap::{Arg, ArgAction, Command};
+        let command = Command::new("test")
+            .arg(Arg::new("fast").long("fast").action(ArgAction::SetTrue))
+            .arg(Arg::new("level").long("level").default_value("1"))
+            .arg(Arg::new("tags").long("tags").num_args(1..))
+            .arg(Arg::new("name").long("name"));
+        let matches = command
+            .clone()
+            .get_matches_from(["test", "--fast", "--tags", "a", "2", "--name", "x"]);
+        let overrides = super::ArgOverrides::from_matches(&command, &matches);
+        let expected = serde_json::json!({"fast": true, "tags": ["a", 2], "name": "x"});
+        assert_eq!(
+            serde_json::Value::Object(overrides.fields().clone()),
+            expected
+        );
+        let value = serde_json::json!({"fast": false, "level": 3, "tags": []});
+        let applied = overrides.apply(&value).unwrap();
+        assert_eq!(
+            applied,
+            serde_json::json!({"fast": true, "level": 3, "tags": ["a", 2]})
+        );
+    }
+    #[test]
+    #[cfg(feature = "clap")]
+    fn test_arg_overrides_types() {
+        use clap::{Arg, ArgAction, Command};
+        #[derive(Debug, PartialEq, serde::Serialize, serde::Deserialize)]
+        struct Settings {
+            tags: Vec<String>,
+            name: String,
+            nick: Option<String>,
+            volume: u32,
+        }
+        let command = Command::new("test")
+            .arg(Arg::new("tags").long("tag").action(ArgAction::Append))
+            .arg(Arg::new("name").long("name"))
+            .arg(Arg::new("nick").long("nick"))
+            .arg(Arg::new("volume").long("volume"));
+        let matches = command.clone().get_matches_from([
+            "test", "--tag", "a", "--name", "123", "--nick", "true", "--volume", "7",
+        ]);
+        let overrides = super::ArgOverrides::from_matches(&command, &matches);
+        assert_eq!(overrides.fields()["tags"], serde_json::json!(["a"]));
+        let settings = Settings {
+            tags: Vec::new(),
+            name: "alice".into(),
+            nick: None,
+            volume: 5,
+        };
+        let expected = Settings {
+            tags: vec!["a".into()],
+            name: "123".into(),
+            nick: Some("true".into()),
+            volume: 7,
+        };
+        assert_eq!(overrides.apply(&settings).unwrap(), expected);
+    }
+    #[test]
+    fn test_env_overrides() {
+        let backend = EnvOverrides::with_prefix(FileBackend::new(), "prefs-test");
+        let name = "tests/env-overrides";
+        let window = serde_json::json!({"title": "x", "size": {"width": 1, "height": 2}});
+        window.save(&APP_INFO, name).unwrap();
+        std::env::set_var("PREFS_TEST_TESTS_ENV_OVERRIDES_TITLE", "y");
+        std::env::set_var("PREFS_TEST_TESTS_ENV_OVERRIDES_SIZE_WIDTH", "3");
+        std::env::set_var("PREFS_TEST_TESTS_ENV_OVERRIDES_MISSING", "4");
+        let loaded = serde_json::Value::load_in(&backend, &APP_INFO, name).unwrap();
+        let expected = serde_json::json!({"title": "y", "size": {"width": 3, "height": 2}});
+        assert_eq!(loaded, expected);
+        assert_eq!(serde_json::Value::load(&APP_INFO, name).unwrap(), window);
+        // Saving keeps the stored value of fields still overridden, but not of changed ones.
+        let mut changed = loaded;
+        changed["size"]["height"] = 5.into();
+        changed["title"] = "z".into();
+        changed["size"]["width"] = 3.0.into();
+        changed.save_in(&backend, &APP_INFO, name).unwrap();
+        let saved = serde_json::json!({"title": "z", "size": {"width": 1, "height": 5}});
+        assert_eq!(serde_json::Value::load(&APP_INFO, name).unwrap(), saved);
+    }
+    #[test]
+    #[cfg(feature = "figment")]
+    fn test_figment_source() {
+        use figment::providers::Serialized;
+        use figment::Figment;
+        let name = "tests/figment-source";
+        serde_json::json!({"a": 1, "b": {"c": 2}})
+            .save(&APP_INFO, name)
+            .unwrap();
+        let figment = Figment::new()
+            .merge(Serialized::defaults(serde_json::json!({"a": 0, "d": 4})))
+            .merge(super::PrefsSource::new(&APP_INFO, name))
+            .merge(super::PrefsSource::new(&APP_INFO, "tests/figment-missing"));
+        let expected = serde_json::json!({"a": 1, "b": {"c": 2}, "d": 4});
+        assert_eq!(
+            serde_json::Value::load_in(&figment, &APP_INFO, "").unwrap(),
+            expected
+        );
+        assert_eq!(u32::load_in(&figment, &APP_INFO, "b/c").unwrap(), 2);
+        assert!(u32::load_in(&figment, &APP_INFO, "e")
+            .unwrap_err()
+            .is_not_found());
+        assert!(4.save_in(&figment, &APP_INFO, "e").is_err());
+    }
+    #[test]
+    #[cfg(feature = "config")]
+    fn test_config_source() {
+        use config::Config;
+        let name = "tests/config-source";
+        serde_json::json!({"a": 1, "b": {"c": 2}})
+            .save(&APP_INFO, name)
+            .unwrap();
+        let config = Config::builder()
+            .set_default("a", 0)
+            .unwrap()
+            .set_default("d", 4)
+            .unwrap()
+            .add_source(super::PrefsSource::new(&APP_INFO, name))
+            .add_source(super::PrefsSource::new(&APP_INFO, "tests/config-missing"))
+            .build()
+            .unwrap();
+        assert_eq!(i64::load_in(&config, &APP_INFO, "a").unwrap(), 1);
+        assert_eq!(i64::load_in(&config, &APP_INFO, "b/c").unwrap(), 2);
+        assert_eq!(i64::load_in(&config, &APP_INFO, "d").unwrap(), 4);
+        assert!(i64::load_in(&config, &APP_INFO, "e")
+            .unwrap_err()
+            .is_not_found());
+        assert!(4.save_in(&config, &APP_INFO, "e").is_err());
+    }
+    #[test]
+    fn test_bundled_defaults() {
+        let name = "tests/bundled-defaults";
+        let defaults = super::BundledDefaults::new().with(name, "{\"a\": 1}");
+        let dir = std::env::temp_dir().join("preferences-bundled-defaults");
+        let plain = FileBackend::with_base_dir(dir);
+        let backend = plain.clone().with_defaults(defaults);
+        plain.remove(&APP_INFO, name).unwrap();
+        let expected = serde_json::json!({"a": 1});
+        let loaded = serde_json::Value::load_in(&backend, &APP_INFO, name).unwrap();
+        assert_eq!(loaded, expected);
+        assert!(serde_json::Value::load_in(&plain, &APP_INFO, name)
+            .unwrap_err()
+            .is_not_found());
+        serde_json::json!({"a": 2})
+            .save_in(&backend, &APP_INFO, name)
+            .unwrap();
+        assert!(i32::load_in(&backend, &APP_INFO, "tests/other").is_err());
+        backend.reset_to_default(&APP_INFO, name).unwrap();
+        let loaded = serde_json::Value::load_in(&backend, &APP_INFO, name).unwrap();
+        assert_eq!(loaded, expected);
+        assert!(plain
+            .reset_to_default(&APP_INFO, name)
+            .unwrap_err()
+            .is_not_found());
+    }
+    #[test]
+    fn test_get_or_insert_with() {
+        let name = "tests/get-or-insert-with";
+        let backend = FileBackend::new();
+        backend.remove(&APP_INFO, name).unwrap();
+        let created = PreferencesMap::<u32>::load_or_insert_with(&APP_INFO, name, || {
+            let mut map = PreferencesMap::new();
+            map.insert("a".into(), 1);
+            map
+        });
+        assert_eq!(created.unwrap()["a"], 1);
+        assert_eq!(
+            super::get_or_insert_with(&APP_INFO, name, "a", || 2).unwrap(),
+            1
+        );
+        assert_eq!(
+            super::get_or_insert_with(&APP_INFO, name, "b", || 3).unwrap(),
+            3
+        );
+        let loaded =
+            PreferencesMap::<u32>::load_or_insert_with(&APP_INFO, name, PreferencesMap::new);
+        assert_eq!(loaded.unwrap().len(), 2);
+    }
+    #[test]
+    fn test_profiles() {
+        let backend = FileBackend::with_base_dir(std::env::temp_dir().join("preferences-profiles"));
+        for name in backend.profiles(&APP_INFO).unwrap() {
+            backend.remove_profile(&APP_INFO, &name).unwrap();
+        }
+        let name = "tests/profiles";
+        0.save_in(&backend, &APP_INFO, name).unwrap();
+        let mut work = super::Profile::with_backend(backend.clone(), "work");
+        1.save_in(&work, &APP_INFO, name).unwrap();
+        let home = work.copy_to(&APP_INFO, "home").unwrap();
+        2.save_in(&home, &APP_INFO, name).unwrap();
+        assert!(work.copy_to(&APP_INFO, "home").is_err());
+        work.rename(&APP_INFO, "office").unwrap();
+        assert_eq!(work.name(), "office");
+        assert_eq!(backend.profiles(&APP_INFO).unwrap(), ["home", "office"]);
+        assert_eq!(i32::load_in(&backend, &APP_INFO, name).unwrap(), 0);
+        assert_eq!(i32::load_in(&work, &APP_INFO, name).unwrap(), 1);
+        assert_eq!(i32::load_in(&home, &APP_INFO, name).unwrap(), 2);
+        home.delete(&APP_INFO).unwrap();
+        assert!(i32::load_in(&home, &APP_INFO, name)
+            .unwrap_err()
+            .is_not_found());
+        assert_eq!(backend.profiles(&APP_INFO).unwrap(), ["office"]);
+        // An empty name names no profile, rather than all of them.
+        let unnamed = super::Profile::with_backend(backend.clone(), "");
+        for result in [
+            unnamed.delete(&APP_INFO),
+            backend.remove_profile(&APP_INFO, ""),
+            0.save_in(&unnamed, &APP_INFO, name),
+        ] {
+            match result {
+                Err(PreferencesError::Io(e)) => {
+                    assert_eq!(e.kind(), std::io::ErrorKind::InvalidInput);
+                }
+                result => panic!("unexpected result: {result:?}"),
+            }
+        }
+        assert_eq!(backend.profiles(&APP_INFO).unwrap(), ["office"]);
+    }
+    #[test]
+    fn test_typed_preferences() {
+        use super::TypedPreferences;
+        let mut prefs = PreferencesMap::<String>::new();
+        prefs.set_f64("ratio", 0.5);
+        prefs.set_display("port", 8080_u16);
+        prefs.insert("flag".into(), "yes".into());
+        assert_eq!(prefs.get_f64("ratio").unwrap(), Some(0.5));
+        assert_eq!(prefs.get_parsed::<u16>("port").unwrap(), Some(8080));
+        assert_eq!(prefs.get_parsed_or::<u8>("port", 1), 1);
+        let err = prefs.get_bool("flag").unwrap_err();
+        assert_eq!(
+            (err.name(), err.value(), err.expected()),
+            ("flag", "yes", "bool")
+        );
+        assert!(prefs.get_bool_or("flag", true));
+        assert_eq!(prefs.get_bool("missing").unwrap(), None);
+    }
+    #[test]
+    fn test_prefs_document() {
+        let mut doc = super::PrefsDocument::new();
+        doc.set("a", 1).unwrap();
+        doc.set("a.b", "x").unwrap();
+        doc.set("a.c", 2.5).unwrap();
+        doc.set("d", [true]).unwrap();
+        let expected = serde_json::json!({"a": {"b": "x", "c": 2.5}, "d": [true]});
+        assert_eq!(serde_json::to_value(&doc).unwrap(), expected);
+        assert_eq!(doc.get_str("a.b"), Some("x"));
+        assert_eq!(doc.get_f64("a.c"), Some(2.5));
+        assert_eq!(doc.get_i64("a.c"), None);
+        assert_eq!(doc.get_array("d").map(Vec::len), Some(1));
+        assert!(doc.get::<u32>("a.b").is_err());
+        assert_eq!(doc.get::<u32>("a.x").unwrap(), None);
+        assert_eq!(doc.remove("a.b"), Some(serde_json::json!("x")));
+        assert_eq!(doc.remove("a.b.c"), None);
+        assert_eq!(doc.remove("d"), Some(serde_json::json!([true])));
+        assert_eq!(doc.as_map().len(), 1);
+    }
+    #[test]
+    fn test_prefs() {
+        let name = "tests/prefs";
+        let backend = FileBackend::new().with_lock_timeout(std::time::Duration::from_millis(50));
+        backend.remove(&APP_INFO, name).unwrap();
+        let mut prefs = super::Prefs::open_locked_in(backend.clone(), &APP_INFO, name).unwrap();
+        assert!(prefs.document().as_map().is_empty());
+        prefs.set("a.b", 1).unwrap();
+        assert!(prefs.is_dirty());
+        prefs.save().unwrap();
+        assert!(!prefs.is_dirty());
+        let entry = prefs.entry("a.c").and_modify(|_| unreachable!());
+        assert!(entry.get().is_none());
+        assert_eq!(*entry.or_insert_with(|| 2).unwrap(), 2);
+        assert_eq!(*prefs.entry("a.c").or_insert(3).unwrap(), 2);
+        assert!(prefs.is_dirty());
+        assert_eq!(prefs.remove("a.c"), Some(serde_json::json!(2)));
+        let result = super::Prefs::open_in(backend.clone(), &APP_INFO, name);
+        assert!(matches!(result, Err(PreferencesError::WouldBlock(_))));
+        prefs.set("a.b", 2).unwrap();
+        prefs.reload().unwrap();
+        assert_eq!(prefs.get::<u32>("a.b").unwrap(), Some(1));
+        assert_eq!(prefs.path(), backend.path(&APP_INFO, name).unwrap());
+        drop(prefs);
+        let prefs = super::Prefs::open_in(backend, &APP_INFO, name).unwrap();
+        assert_eq!(prefs.get::<u32>("a.b").unwrap(), Some(1));
+    }
+    #[test]
+    fn test_prefs_guard() {
+        let name = "tests/prefs-guard";
+        1.save(&APP_INFO, name).unwrap();
+        let mut guard = super::PrefsGuard::<i32>::load(&APP_INFO, name).unwrap();
+        *guard += 1;
+        guard.discard();
+        assert_eq!(i32::load(&APP_INFO, name).unwrap(), 1);
+        let mut guard = super::PrefsGuard::<i32>::load(&APP_INFO, name).unwrap();
+        *guard += 1;
+        drop(guard);
+        assert_eq!(i32::load(&APP_INFO, name).unwrap(), 2);
+        let panicked = std::thread::spawn(move || {
+            let mut guard = super::PrefsGuard::<i32>::load(&APP_INFO, name).unwrap();
+            *guard = -1;
+            panic!("interrupted mid-edit");
+        })
+        .join();
+        assert!(panicked.is_err());
+        assert_eq!(i32::load(&APP_INFO, name).unwrap(), 2);
+        let guard = super::PrefsGuard::<i32>::load(&APP_INFO, name).unwrap();
+        guard.commit().unwrap();
+        assert_eq!(i32::load(&APP_INFO, name).unwrap(), 2);
+        let backend = super::ReadOnly::new(FileBackend::new());
+        let mut guard = super::PrefsGuard::<i32, _>::load_in(backend, &APP_INFO, name).unwrap();
+        *guard = 3;
+        assert!(guard.commit().is_err());
+    }
+    #[test]
+    fn test_edit() {
+        let name = "tests/edit";
+        FileBackend::new().remove(&APP_INFO, name).unwrap();
+        let threads: Vec<_> = (0..4)
+            .map(|_| {
+                std::thread::spawn(move || {
+                    for _ in 0..10 {
+                        super::edit(&APP_INFO, name, |count: &mut u32| *count += 1).unwrap();
+                    }
+                })
+            })
+            .collect();
+        for thread in threads {
+            thread.join().unwrap();
+        }
+        assert_eq!(u32::load(&APP_INFO, name).unwrap(), 40);
+    }
+    #[test]
+    fn test_update_field() {
+        let name = "tests/update-field";
+        FileBackend::new().remove(&APP_INFO, name).unwrap();
+        super::update_field(&APP_INFO, name, "a.b", 1).unwrap();
+        super::update_field(&APP_INFO, name, "c", [2]).unwrap();
+        let saved = serde_json::Value::load(&APP_INFO, name).unwrap();
+        assert_eq!(saved, serde_json::json!({"a": {"b": 1}, "c": [2]}));
+        3.save(&APP_INFO, name).unwrap();
+        assert!(super::update_field(&APP_INFO, name, "a", 1).is_err());
+        assert_eq!(i32::load(&APP_INFO, name).unwrap(), 3);
+    }
 }
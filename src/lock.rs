@@ -0,0 +1,123 @@
+use crate::{FileBackend, PreferencesError};
+use app_dirs::AppInfo;
+use fs2::FileExt;
+use std::fs::create_dir_all;
+use std::fs::{File, OpenOptions};
+use std::io;
+use std::path::{Path, PathBuf};
+use std::thread;
+use std::time::{Duration, Instant};
+
+static LOCK_FILE_EXTENSION: &str = ".lock";
+static INSTANCE_FILE_EXTENSION: &str = ".instance";
+const RETRY_INTERVAL: Duration = Duration::from_millis(10);
+
+/// Whether a lock allows other holders at the same time.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LockMode {
+    Shared,
+    Exclusive,
+}
+
+/// Returns the path of the lock file guarding `path`.
+pub fn lock_path(path: &Path) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(LOCK_FILE_EXTENSION);
+    name.into()
+}
+
+/// Takes an advisory lock on the lock file guarding `path`, waiting up to `timeout` (or for as
+/// long as it takes, if `None`) for other processes to release it. The lock is held until the
+/// returned file is dropped.
+///
+/// A separate lock file is used so that the data file itself can be truncated or replaced
+/// while locked.
+pub fn lock(
+    path: &Path,
+    key: &str,
+    mode: LockMode,
+    timeout: Option<Duration>,
+) -> Result<File, PreferencesError> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .create(true)
+        .truncate(false)
+        .open(lock_path(path))?;
+    let deadline = timeout.map(|timeout| Instant::now() + timeout);
+    loop {
+        let result = match (mode, deadline) {
+            (LockMode::Shared, None) => FileExt::lock_shared(&file),
+            (LockMode::Exclusive, None) => FileExt::lock_exclusive(&file),
+            (LockMode::Shared, Some(_)) => FileExt::try_lock_shared(&file),
+            (LockMode::Exclusive, Some(_)) => FileExt::try_lock_exclusive(&file),
+        };
+        match result {
+            Ok(()) => return Ok(file),
+            Err(e) if !is_contended(&e) => return Err(e.into()),
+            Err(_) if deadline.map_or(false, |deadline| Instant::now() >= deadline) => {
+                return Err(PreferencesError::WouldBlock(key.into()))
+            }
+            Err(_) => thread::sleep(RETRY_INTERVAL),
+        }
+    }
+}
+
+fn is_contended(e: &io::Error) -> bool {
+    e.raw_os_error() == fs2::lock_contended_error().raw_os_error()
+}
+
+/// App-wide lock ensuring that only one instance of the app writes preferences at a time.
+///
+/// Acquire the lock on startup and keep the guard alive for as long as the app runs. If another
+/// instance already holds it, acquiring fails with
+/// [`PreferencesError::WouldBlock`](enum.PreferencesError.html#variant.WouldBlock), and the app
+/// can fall back to read-only mode instead of fighting over the files. The lock is released
+/// when the guard is dropped, or when the process exits.
+///
+/// Like [`FileBackend::with_lock_timeout`](struct.FileBackend.html#method.with_lock_timeout),
+/// this lock is advisory: it doesn't stop writes from instances that don't acquire it.
+///
+/// ```
+/// use preferences::{AppInfo, PreferencesError, PrefsLockGuard};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let read_only = match PrefsLockGuard::acquire(&APP_INFO) {
+///     Ok(_guard) => false, // Keep the guard around while the app runs
+///     Err(PreferencesError::WouldBlock(_)) => true,
+///     Err(e) => panic!("{e}"),
+/// };
+/// # assert!(!read_only);
+/// ```
+#[derive(Debug)]
+pub struct PrefsLockGuard {
+    _file: File,
+}
+
+impl PrefsLockGuard {
+    /// Takes the lock of `app`, without waiting if another instance holds it.
+    ///
+    /// # Errors
+    /// If another instance holds the lock, or if the lock file can't be created.
+    pub fn acquire(app: &AppInfo) -> Result<Self, PreferencesError> {
+        Self::acquire_in(&FileBackend::new(), app, Duration::ZERO)
+    }
+
+    /// Same as `acquire`, but places the lock file next to the data of `backend`, and waits up
+    /// to `timeout` for another instance to release the lock.
+    ///
+    /// # Errors
+    /// If another instance still holds the lock after `timeout`, or if the lock file can't be
+    /// created.
+    pub fn acquire_in(
+        backend: &FileBackend,
+        app: &AppInfo,
+        timeout: Duration,
+    ) -> Result<Self, PreferencesError> {
+        let path = backend.app_file(app, INSTANCE_FILE_EXTENSION)?;
+        path.parent().map(create_dir_all);
+        Ok(Self {
+            _file: lock(&path, app.name, LockMode::Exclusive, Some(timeout))?,
+        })
+    }
+}
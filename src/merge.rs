@@ -0,0 +1,170 @@
+use crate::{Backend, FileBackend, PreferencesError, PreferencesMap};
+use app_dirs::AppInfo;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::{Map, Value};
+use std::collections::hash_map::Entry;
+
+/// How [`merge`](fn.merge.html) and [`merge_maps`](fn.merge_maps.html) combine two values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+#[non_exhaustive]
+pub enum MergeStrategy {
+    /// Each top-level field (or map entry) of the new value replaces the existing one as a whole.
+    Replace,
+    /// Objects are merged field by field, at every level. Anything else is replaced.
+    Deep,
+    /// Like `Deep`, but arrays are appended to rather than replaced.
+    AppendArrays,
+    /// Like `Deep`, but existing values are kept: only missing fields are added.
+    KeepExisting,
+}
+
+/// Merges `other` into `base` according to `strategy`.
+///
+/// ```
+/// use preferences::{merge, MergeStrategy};
+/// use serde_json::json;
+///
+/// let mut base = json!({"window": {"width": 640, "height": 480}, "recent": ["a"]});
+/// let other = json!({"window": {"width": 800}, "recent": ["b"]});
+///
+/// merge(&mut base, other, MergeStrategy::AppendArrays);
+/// assert_eq!(base, json!({"window": {"width": 800, "height": 480}, "recent": ["a", "b"]}));
+/// ```
+pub fn merge(base: &mut Value, other: Value, strategy: MergeStrategy) {
+    match (base, other) {
+        (&mut Value::Object(ref mut base), Value::Object(other)) => {
+            for (name, value) in other {
+                match base.get_mut(&name) {
+                    Some(existing) if strategy != MergeStrategy::Replace => {
+                        merge(existing, value, strategy);
+                    }
+                    _ => {
+                        base.insert(name, value);
+                    }
+                }
+            }
+        }
+        (&mut Value::Array(ref mut base), Value::Array(other))
+            if strategy == MergeStrategy::AppendArrays =>
+        {
+            base.extend(other);
+        }
+        (base, other) => {
+            if strategy != MergeStrategy::KeepExisting {
+                *base = other;
+            }
+        }
+    }
+}
+
+/// Merges the entries of `other` into `base` according to `strategy`.
+///
+/// Entries present in both maps are merged through their JSON representation, except with
+/// `Replace`, which doesn't need to look inside them.
+///
+/// # Errors
+/// If an entry can't be serialized, or if a merged entry can't be deserialized.
+pub fn merge_maps<T>(
+    base: &mut PreferencesMap<T>,
+    other: PreferencesMap<T>,
+    strategy: MergeStrategy,
+) -> Result<(), PreferencesError>
+where
+    T: Serialize + DeserializeOwned,
+{
+    for (name, value) in other {
+        match base.entry(name) {
+            Entry::Vacant(entry) => {
+                entry.insert(value);
+            }
+            Entry::Occupied(mut entry) => {
+                if strategy == MergeStrategy::Replace {
+                    entry.insert(value);
+                } else {
+                    let mut merged = serde_json::to_value(entry.get())?;
+                    merge(&mut merged, serde_json::to_value(value)?, strategy);
+                    entry.insert(serde_json::from_value(merged)?);
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Applies `patch` to `target` as a JSON Merge Patch ([RFC 7396]).
+///
+/// Fields of `patch` replace those of `target`, nested objects are patched field by field, and
+/// fields set to `null` in `patch` are removed from `target`.
+///
+/// [RFC 7396]: https://www.rfc-editor.org/rfc/rfc7396
+pub fn merge_patch(target: &mut Value, patch: &Value) {
+    match *patch {
+        Value::Object(ref patch) => {
+            if !target.is_object() {
+                *target = Value::Object(Map::new());
+            }
+            if let Value::Object(ref mut target) = *target {
+                for (name, value) in patch {
+                    if value.is_null() {
+                        target.remove(name);
+                    } else {
+                        merge_patch(target.entry(name.clone()).or_insert(Value::Null), value);
+                    }
+                }
+            }
+        }
+        _ => *target = patch.clone(),
+    }
+}
+
+/// Updates the data saved under `key` with a JSON Merge Patch (see
+/// [`merge_patch`](fn.merge_patch.html)), without deserializing it into a Rust type. Missing
+/// data is patched as if it were `null`.
+///
+/// ```
+/// use preferences::{apply_patch, AppInfo, Preferences, PreferencesMap};
+/// use serde_json::json;
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let key = "tests/docs/patch";
+/// json!({"volume": 5, "theme": "dark", "legacy": true}).save(&APP_INFO, key).unwrap();
+/// apply_patch(&APP_INFO, key, &json!({"volume": 8, "legacy": null})).unwrap();
+/// let saved = serde_json::Value::load(&APP_INFO, key).unwrap();
+/// assert_eq!(saved, json!({"volume": 8, "theme": "dark"}));
+/// ```
+///
+/// # Errors
+/// If the saved data isn't valid JSON, or if a file I/O error (e.g. permission denied) occurs.
+pub fn apply_patch<S: AsRef<str>>(
+    app: &AppInfo,
+    key: S,
+    patch: &Value,
+) -> Result<(), PreferencesError> {
+    apply_patch_in(&FileBackend::new(), app, key, patch)
+}
+
+/// Same as `apply_patch`, but updates data saved in an arbitrary
+/// [`Backend`](trait.Backend.html).
+///
+/// # Errors
+/// If the saved data isn't valid JSON, or if the backend fails to read or write it.
+pub fn apply_patch_in<B, S>(
+    backend: &B,
+    app: &AppInfo,
+    key: S,
+    patch: &Value,
+) -> Result<(), PreferencesError>
+where
+    B: Backend + ?Sized,
+    S: AsRef<str>,
+{
+    let key = key.as_ref();
+    let mut value = match backend.read(app, key) {
+        Ok(data) => serde_json::from_slice(&data)?,
+        Err(e) if e.is_not_found() => Value::Null,
+        Err(e) => return Err(e),
+    };
+    merge_patch(&mut value, patch);
+    backend.write(app, key, &serde_json::to_vec(&value)?)
+}
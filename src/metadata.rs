@@ -0,0 +1,150 @@
+use serde::{Deserialize, Serialize};
+use std::env;
+use std::fs;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Extra information recorded next to a preferences file.
+///
+/// Metadata lives in a separate file, so it never changes the saved data itself: programs
+/// that only know about the plain JSON format keep reading it unaffected. Use
+/// [`FileBackend::metadata`](struct.FileBackend.html#method.metadata) to read it back.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Metadata {
+    /// Which build of which program wrote the file, if recorded.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub provenance: Option<Provenance>,
+    /// Format of the saved data, if it was saved with
+    /// [`FileBackend::save_raw`](struct.FileBackend.html#method.save_raw). `None` means the
+    /// data was serialized as JSON by [`Preferences`](trait.Preferences.html).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub content_type: Option<ContentType>,
+    /// CRC-32 of the saved data, if it was saved by a
+    /// [`FileBackend`](struct.FileBackend.html) configured with
+    /// [`with_checksums`](struct.FileBackend.html#method.with_checksums).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<u32>,
+    /// Hex-encoded HMAC-SHA256 of the saved data, if it was saved by a
+    /// [`FileBackend`](struct.FileBackend.html) configured with
+    /// [`with_signing_key`](struct.FileBackend.html#method.with_signing_key).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub signature: Option<String>,
+    /// Versions of the file format and of the app's data, if it was saved by a
+    /// [`FileBackend`](struct.FileBackend.html) configured with
+    /// [`with_schema_version`](struct.FileBackend.html#method.with_schema_version).
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<VersionHeader>,
+}
+
+impl Metadata {
+    pub(crate) const fn is_empty(&self) -> bool {
+        self.provenance.is_none()
+            && self.content_type.is_none()
+            && self.checksum.is_none()
+            && self.signature.is_none()
+            && self.version.is_none()
+    }
+}
+
+/// Version of the crate's file format written by this release.
+pub const FORMAT_VERSION: u32 = 1;
+
+/// Versions recorded with a saved file, so that future releases of an app (and of this crate)
+/// can tell which layout the data has.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct VersionHeader {
+    /// Version of this crate's file format.
+    pub format: u32,
+    /// Version of the app's data, as passed to `with_schema_version(..)`.
+    pub schema: u32,
+}
+
+impl VersionHeader {
+    pub(crate) const fn current(schema: u32) -> Self {
+        Self {
+            format: FORMAT_VERSION,
+            schema,
+        }
+    }
+
+    /// Returns `true` if data with this header was written by a newer release of the app or of
+    /// this crate than one supporting `schema`.
+    pub(crate) const fn is_newer_than(self, schema: u32) -> bool {
+        self.format > FORMAT_VERSION || self.schema > schema
+    }
+}
+
+/// Declared format of data saved with
+/// [`FileBackend::save_raw`](struct.FileBackend.html#method.save_raw).
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ContentType {
+    /// JSON text.
+    Json,
+    /// TOML text.
+    Toml,
+    /// Any other UTF-8 text.
+    Text,
+    /// Arbitrary bytes.
+    Binary,
+}
+
+/// Watermark identifying the program that last wrote a preferences file.
+///
+/// Recorded on every save by a [`FileBackend`](struct.FileBackend.html) configured with
+/// [`with_provenance`](struct.FileBackend.html#method.with_provenance).
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[non_exhaustive]
+pub struct Provenance {
+    /// Version of the application, as passed to `with_provenance(..)`.
+    pub app_version: String,
+    /// Version of this crate.
+    pub crate_version: String,
+    /// Time of the save, in seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// Hash of the machine's host name, if it could be determined.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname_hash: Option<u64>,
+}
+
+impl Provenance {
+    pub(crate) fn current(app_version: &str) -> Self {
+        Self {
+            app_version: app_version.into(),
+            crate_version: env!("CARGO_PKG_VERSION").into(),
+            timestamp: unix_time(),
+            hostname_hash: hostname().map(|name| fnv1a(name.trim().as_bytes())),
+        }
+    }
+}
+
+pub fn unix_time() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |elapsed| elapsed.as_secs())
+}
+
+fn hostname() -> Option<String> {
+    env::var("COMPUTERNAME")
+        .or_else(|_| env::var("HOSTNAME"))
+        .ok()
+        .or_else(|| fs::read_to_string("/etc/hostname").ok())
+        .filter(|name| !name.trim().is_empty())
+}
+
+/// 64-bit FNV-1a, which (unlike `DefaultHasher`) is stable across Rust releases.
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// CRC-32 (IEEE), as used by zip and PNG.
+pub fn crc32(bytes: &[u8]) -> u32 {
+    !bytes.iter().fold(!0, |crc, &byte| {
+        (0..8).fold(crc ^ u32::from(byte), |crc, _| {
+            (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg())
+        })
+    })
+}
@@ -0,0 +1,197 @@
+use crate::{Backend, FileBackend, Preferences, PreferencesError};
+use app_dirs::AppInfo;
+use serde_json::Value;
+use std::fmt;
+use std::fs;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
+
+type Step = Box<dyn Fn(&mut Value) + Send + Sync>;
+
+/// Upgrades saved data from older schema versions to the current one.
+///
+/// Each step converts data from one schema version to the next, working on its JSON
+/// representation. Data saved without a [`VersionHeader`](struct.VersionHeader.html) counts as
+/// version 1, and the current version is one past the last step. Migrated data is saved back
+/// with the current version, so each step runs at most once per file.
+///
+/// Before a file is migrated, its old contents are copied next to it with the old version as a
+/// suffix (e.g. `profile.prefs.json.v2`), so a faulty migration can be undone with
+/// [`rollback_migration`](#method.rollback_migration).
+///
+/// ```
+/// use preferences::{AppInfo, Migrator, Preferences, PreferencesMap};
+/// use serde::{Deserialize, Serialize};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// #[derive(Serialize, Deserialize)]
+/// struct Profile {
+///     username: String,
+///     theme: String,
+/// }
+///
+/// let key = "tests/docs/migrate";
+/// # preferences::FileBackend::new().remove(&APP_INFO, key).unwrap();
+/// let mut old = PreferencesMap::new();
+/// old.insert("name".to_owned(), "alice".to_owned());
+/// old.save(&APP_INFO, key).unwrap();
+///
+/// let migrator = Migrator::new()
+///     .step(1, |value| {
+///         let name = value["name"].take();
+///         value["username"] = name;
+///     })
+///     .step(2, |value| value["theme"] = "dark".into());
+/// let profile = Profile::load_with_migrations(&migrator, &APP_INFO, key).unwrap();
+/// assert_eq!(profile.username, "alice");
+/// assert_eq!(profile.theme, "dark");
+/// ```
+pub struct Migrator {
+    backend: FileBackend,
+    steps: Vec<Step>,
+    version: u32,
+}
+
+impl Migrator {
+    /// Creates a migrator without any steps, for the default
+    /// [`FileBackend`](struct.FileBackend.html).
+    #[must_use]
+    pub const fn new() -> Self {
+        Self::with_backend(FileBackend::new())
+    }
+
+    /// Creates a migrator without any steps, for data saved in `backend`.
+    #[must_use]
+    pub const fn with_backend(backend: FileBackend) -> Self {
+        Self {
+            backend,
+            steps: Vec::new(),
+            version: 1,
+        }
+    }
+
+    /// Adds a step upgrading data from schema version `from` to `from + 1`.
+    ///
+    /// # Panics
+    /// If `from` isn't the current version, i.e. if steps aren't added in order starting from 1.
+    #[must_use]
+    pub fn step<F>(mut self, from: u32, migrate: F) -> Self
+    where
+        F: Fn(&mut Value) + Send + Sync + 'static,
+    {
+        assert_eq!(from, self.version, "migration steps must be added in order");
+        self.steps.push(Box::new(migrate));
+        self.version += 1;
+        self
+    }
+
+    /// Returns the current schema version, which migrated data is saved with.
+    #[must_use]
+    pub const fn version(&self) -> u32 {
+        self.version
+    }
+
+    /// Upgrades the data saved under `key` to the current version, if it's older, and saves it
+    /// back.
+    ///
+    /// Returns `true` if the data was migrated. Keys without data, and data saved with a newer
+    /// version, are left alone.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs, or if the saved data isn't valid JSON.
+    pub fn migrate(&self, app: &AppInfo, key: &str) -> Result<bool, PreferencesError> {
+        let backend = self.backend();
+        let from = backend
+            .metadata(app, key)?
+            .version
+            .map_or(1, |version| version.schema.max(1));
+        if from >= self.version {
+            return Ok(false);
+        }
+        let data = match backend.load_raw(app, key) {
+            Err(e) if e.is_not_found() => return Ok(false),
+            result => result?,
+        };
+        let mut value: Value = serde_json::from_slice(&data)?;
+        fs::write(snapshot_path(&backend.path(app, key)?, from), &data)?;
+        for step in self.steps.iter().skip(from as usize - 1) {
+            step(&mut value);
+        }
+        value.save_in(&backend, app, key)?;
+        Ok(true)
+    }
+
+    /// Restores the data saved under `key` from before its most recent migration, and returns
+    /// the version it had then. Returns `None` if there is nothing to roll back.
+    ///
+    /// Each call walks one migration further back. Loading the data with this migrator migrates
+    /// it again, so roll back after fixing the faulty step (or with an older release of the app).
+    ///
+    /// ```
+    /// use preferences::{AppInfo, Migrator, Preferences};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let key = "tests/docs/rollback-migration";
+    /// # preferences::FileBackend::new().remove(&APP_INFO, key).unwrap();
+    /// 10.save(&APP_INFO, key).unwrap();
+    /// let buggy = Migrator::new().step(1, |value| *value = 0.into());
+    /// assert_eq!(i32::load_with_migrations(&buggy, &APP_INFO, key).unwrap(), 0);
+    /// assert_eq!(buggy.rollback_migration(&APP_INFO, key).unwrap(), Some(1));
+    /// assert_eq!(i32::load(&APP_INFO, key).unwrap(), 10);
+    /// ```
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs.
+    pub fn rollback_migration(
+        &self,
+        app: &AppInfo,
+        key: &str,
+    ) -> Result<Option<u32>, PreferencesError> {
+        let path = self.backend.path(app, key)?;
+        for version in (1..self.version).rev() {
+            let snapshot = snapshot_path(&path, version);
+            let data = match fs::read(&snapshot) {
+                Err(e) if e.kind() == ErrorKind::NotFound => continue,
+                result => result?,
+            };
+            self.backend
+                .clone()
+                .with_schema_version(version)
+                .write(app, key, &data)?;
+            fs::remove_file(snapshot)?;
+            return Ok(Some(version));
+        }
+        Ok(None)
+    }
+
+    /// Returns the backend, configured to record the current version.
+    ///
+    /// Save data in the current schema through it, so that it's recorded with the current
+    /// version. (Saving through a backend without a schema version keeps the version the file
+    /// already had.)
+    #[must_use]
+    pub fn backend(&self) -> FileBackend {
+        self.backend.clone().with_schema_version(self.version)
+    }
+}
+
+fn snapshot_path(path: &Path, version: u32) -> PathBuf {
+    let mut name = path.as_os_str().to_owned();
+    name.push(format!(".v{version}"));
+    name.into()
+}
+
+impl Default for Migrator {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl fmt::Debug for Migrator {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Migrator")
+            .field("backend", &self.backend)
+            .field("version", &self.version)
+            .finish_non_exhaustive()
+    }
+}
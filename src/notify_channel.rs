@@ -0,0 +1,197 @@
+use crate::PreferencesError;
+use app_dirs::{sanitized, AppInfo};
+use std::env;
+use std::fs::{self, DirBuilder};
+use std::io::{self, ErrorKind};
+use std::os::unix::fs::{DirBuilderExt, MetadataExt};
+use std::os::unix::net::UnixDatagram;
+use std::path::{Path, PathBuf};
+use std::process;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+static SOCKET_FILE_EXTENSION: &str = "sock";
+const MAX_KEY_LEN: usize = 4096;
+
+/// Sender side of an app's change notification channel. (Requires the `watch` feature, and a
+/// Unix platform.)
+///
+/// File notifications (see [`watch`](fn.watch.html)) can be slow or missing on some file
+/// systems, network mounts in particular. This channel lets one instance of an app tell every
+/// other running instance "this key changed" directly, through Unix domain sockets, so they can
+/// reload it right away. The sockets live in the user's runtime directory (or the temporary
+/// directory), never next to the data itself, in a subdirectory that only the user can access.
+/// If another user created that subdirectory first, or can access it, the channel refuses to
+/// use it.
+///
+/// ```
+/// use preferences::{AppInfo, ChangeListener, ChangeNotifier, Preferences};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// // In every instance:
+/// let listener = ChangeListener::bind(&APP_INFO).unwrap();
+///
+/// // In the instance that saves:
+/// 11.save(&APP_INFO, "tests/docs/notify").unwrap();
+/// ChangeNotifier::new(&APP_INFO).notify("tests/docs/notify").unwrap();
+///
+/// assert_eq!(listener.recv().unwrap(), "tests/docs/notify");
+/// ```
+#[derive(Clone, Debug)]
+pub struct ChangeNotifier {
+    dir: PathBuf,
+}
+
+impl ChangeNotifier {
+    /// Creates a notifier for the instances of `app`.
+    #[must_use]
+    pub fn new(app: &AppInfo) -> Self {
+        Self {
+            dir: channel_dir(app),
+        }
+    }
+
+    /// Tells every listening instance that the data saved under `key` changed, and returns how
+    /// many were told.
+    ///
+    /// Listeners that are too far behind to accept the message are skipped, and sockets left
+    /// behind by instances that crashed are cleaned up.
+    ///
+    /// # Errors
+    /// If the listeners can't be enumerated (e.g. because the channel's directory isn't private
+    /// to the user), or if a message can't be sent for any other reason.
+    pub fn notify(&self, key: &str) -> Result<usize, PreferencesError> {
+        match check_private(&self.dir) {
+            Err(e) if e.kind() == ErrorKind::NotFound => return Ok(0),
+            result => result?,
+        }
+        let entries = fs::read_dir(&self.dir)?;
+        let socket = UnixDatagram::unbound()?;
+        socket.set_nonblocking(true)?;
+        let mut told = 0;
+        for entry in entries {
+            let path = entry?.path();
+            if path
+                .extension()
+                .map_or(true, |ext| ext != SOCKET_FILE_EXTENSION)
+            {
+                continue;
+            }
+            match socket.send_to(key.as_bytes(), &path) {
+                Ok(_) => told += 1,
+                Err(e) if e.kind() == ErrorKind::ConnectionRefused => {
+                    let _ = fs::remove_file(&path);
+                }
+                Err(e) if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::NotFound) => {}
+                Err(e) => return Err(e.into()),
+            }
+        }
+        Ok(told)
+    }
+}
+
+/// Receiver side of an app's change notification channel, created by each instance that wants
+/// to hear about changes. (Requires the `watch` feature, and a Unix platform.)
+///
+/// See [`ChangeNotifier`](struct.ChangeNotifier.html). Each listener receives the keys passed to
+/// `notify(..)`, in order, until it's dropped.
+#[derive(Debug)]
+pub struct ChangeListener {
+    socket: UnixDatagram,
+    path: PathBuf,
+}
+
+impl ChangeListener {
+    /// Starts listening for the change notifications of `app`.
+    ///
+    /// # Errors
+    /// If the socket can't be created, or if the channel's directory isn't private to the user.
+    pub fn bind(app: &AppInfo) -> Result<Self, PreferencesError> {
+        static NEXT_LISTENER: AtomicUsize = AtomicUsize::new(0);
+        let dir = channel_dir(app);
+        match DirBuilder::new().mode(0o700).create(&dir) {
+            Err(e) if e.kind() != ErrorKind::AlreadyExists => return Err(e.into()),
+            _ => check_private(&dir)?,
+        }
+        let name = format!(
+            "{}-{}.{}",
+            process::id(),
+            NEXT_LISTENER.fetch_add(1, Ordering::Relaxed),
+            SOCKET_FILE_EXTENSION
+        );
+        let path = dir.join(name);
+        // A previous process with the same ID may have left its socket behind.
+        let _ = fs::remove_file(&path);
+        let socket = UnixDatagram::bind(&path)?;
+        Ok(Self { socket, path })
+    }
+
+    /// Waits for the next notification, and returns its key.
+    ///
+    /// # Errors
+    /// If the socket fails.
+    pub fn recv(&self) -> Result<String, PreferencesError> {
+        let mut buf = [0; MAX_KEY_LEN];
+        let len = self.socket.recv(&mut buf)?;
+        Ok(String::from_utf8_lossy(&buf[..len]).into_owned())
+    }
+
+    /// Same as `recv`, but gives up after `timeout`, returning `None`.
+    ///
+    /// # Errors
+    /// If the socket fails.
+    pub fn recv_timeout(&self, timeout: Duration) -> Result<Option<String>, PreferencesError> {
+        self.socket.set_read_timeout(Some(timeout))?;
+        let result = self.recv();
+        self.socket.set_read_timeout(None)?;
+        match result {
+            Err(PreferencesError::Io(ref e))
+                if matches!(e.kind(), ErrorKind::WouldBlock | ErrorKind::TimedOut) =>
+            {
+                Ok(None)
+            }
+            result => result.map(Some),
+        }
+    }
+
+    /// Returns the path of this listener's socket.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl Drop for ChangeListener {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.path);
+    }
+}
+
+fn channel_dir(app: &AppInfo) -> PathBuf {
+    let base = env::var_os("XDG_RUNTIME_DIR").map_or_else(env::temp_dir, PathBuf::from);
+    base.join(format!(
+        "{}.{}.notify",
+        sanitized(app.author),
+        sanitized(app.name)
+    ))
+}
+
+/// Fails unless `dir` is a directory owned by the current user that no one else can access.
+///
+/// The temporary directory is shared by every user, so another one could create the channel's
+/// directory first, then read the keys sent through it or plant sockets of their own.
+#[allow(clippy::verbose_bit_mask)] // spelled out, so the permission check reads at a glance
+fn check_private(dir: &Path) -> io::Result<()> {
+    let metadata = fs::symlink_metadata(dir)?;
+    // SAFETY: `geteuid` has no preconditions and can't fail.
+    let uid = unsafe { libc::geteuid() };
+    let private = metadata.mode() & 0o077 == 0;
+    if metadata.is_dir() && metadata.uid() == uid && private {
+        return Ok(());
+    }
+    let msg = format!(
+        "{}: not a private directory of the current user",
+        dir.display()
+    );
+    Err(io::Error::new(ErrorKind::PermissionDenied, msg))
+}
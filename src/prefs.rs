@@ -0,0 +1,290 @@
+use crate::{FileBackend, PreferencesError, PrefsDocument};
+use app_dirs::AppInfo;
+use serde::de::DeserializeOwned;
+use serde::Serialize;
+use serde_json::Value;
+use std::fs::{create_dir_all, File};
+use std::path::{Path, PathBuf};
+
+/// Handle to the [`PrefsDocument`](struct.PrefsDocument.html) saved under one key, opened once
+/// and used many times.
+///
+/// The file's path is resolved (and its directory created) when the handle is opened, rather
+/// than on every save and load. Changes are made to the document in memory, and only written
+/// with [`save`](#method.save). [`is_dirty`](#method.is_dirty) tells whether there are changes
+/// left to save.
+///
+/// A handle opened with [`open_locked`](#method.open_locked) also holds the key's advisory lock
+/// until it's dropped, so that other processes locking the same files (see
+/// [`FileBackend::with_lock_timeout`](struct.FileBackend.html#method.with_lock_timeout)) wait
+/// for it rather than interleave their changes with this one.
+///
+/// ```
+/// use preferences::{AppInfo, Prefs};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let mut prefs = Prefs::open(&APP_INFO, "tests/docs/prefs").unwrap();
+/// let launches = prefs.get::<u32>("launches").unwrap().unwrap_or(0);
+/// prefs.set("launches", launches + 1).unwrap();
+/// prefs.save().unwrap();
+///
+/// prefs.reload().unwrap();
+/// assert_eq!(prefs.get::<u32>("launches").unwrap(), Some(launches + 1));
+/// ```
+#[derive(Debug)]
+pub struct Prefs {
+    backend: FileBackend,
+    app: AppInfo,
+    key: String,
+    path: PathBuf,
+    document: PrefsDocument,
+    dirty: bool,
+    _lock: Option<File>,
+}
+
+impl Prefs {
+    /// Opens the document saved under `key` in the default
+    /// [`FileBackend`](struct.FileBackend.html). A key without saved data opens as an empty
+    /// document.
+    ///
+    /// # Errors
+    /// If the data exists but can't be read, or isn't a JSON object.
+    pub fn open<S: Into<String>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError> {
+        Self::open_in(FileBackend::new(), app, key)
+    }
+
+    /// Same as `open`, but uses `backend`.
+    ///
+    /// # Errors
+    /// If the data exists but can't be read, or isn't a JSON object.
+    pub fn open_in<S: Into<String>>(
+        backend: FileBackend,
+        app: &AppInfo,
+        key: S,
+    ) -> Result<Self, PreferencesError> {
+        Self::open_with(backend, app, key.into(), false)
+    }
+
+    /// Same as `open`, but also takes the key's lock, holding it until the handle is dropped.
+    ///
+    /// # Errors
+    /// If the data exists but can't be read, or isn't a JSON object, or if the lock can't be
+    /// taken.
+    pub fn open_locked<S: Into<String>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError> {
+        Self::open_locked_in(FileBackend::new(), app, key)
+    }
+
+    /// Same as `open_locked`, but uses `backend`, waiting up to its lock timeout (if any) for
+    /// the lock.
+    ///
+    /// # Errors
+    /// If the data exists but can't be read, or isn't a JSON object, or if the lock can't be
+    /// taken in time.
+    pub fn open_locked_in<S: Into<String>>(
+        backend: FileBackend,
+        app: &AppInfo,
+        key: S,
+    ) -> Result<Self, PreferencesError> {
+        Self::open_with(backend, app, key.into(), true)
+    }
+
+    fn open_with(
+        backend: FileBackend,
+        app: &AppInfo,
+        key: String,
+        locked: bool,
+    ) -> Result<Self, PreferencesError> {
+        let path = backend.path(app, &key)?;
+        path.parent().map(create_dir_all);
+        let (backend, lock) = if locked {
+            let (backend, lock) = backend.hold_lock(&path, &key)?;
+            (backend, Some(lock))
+        } else {
+            (backend, None)
+        };
+        let mut prefs = Self {
+            backend,
+            app: app.clone(),
+            key,
+            path,
+            document: PrefsDocument::new(),
+            dirty: false,
+            _lock: lock,
+        };
+        prefs.reload()?;
+        Ok(prefs)
+    }
+
+    /// Deserializes the value at `path` (see [`PrefsDocument::get`](struct.PrefsDocument.html#method.get)).
+    ///
+    /// # Errors
+    /// If the value exists but can't be deserialized as a `T`.
+    pub fn get<T: DeserializeOwned>(&self, path: &str) -> Result<Option<T>, PreferencesError> {
+        self.document.get(path)
+    }
+
+    /// Sets the value at `path` (see [`PrefsDocument::set`](struct.PrefsDocument.html#method.set)).
+    /// The change isn't saved until [`save`](#method.save) is called.
+    ///
+    /// # Errors
+    /// If `value` can't be serialized.
+    pub fn set<V: Serialize>(&mut self, path: &str, value: V) -> Result<(), PreferencesError> {
+        self.document.set(path, value)?;
+        self.dirty = true;
+        Ok(())
+    }
+
+    /// Removes the value at `path`, returning it if it existed. The change isn't saved until
+    /// [`save`](#method.save) is called.
+    pub fn remove(&mut self, path: &str) -> Option<Value> {
+        let value = self.document.remove(path);
+        self.dirty |= value.is_some();
+        value
+    }
+
+    /// Returns the entry at `path`, for reading, defaulting, or changing it in place.
+    ///
+    /// ```
+    /// use preferences::{AppInfo, Prefs};
+    /// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+    ///
+    /// let mut prefs = Prefs::open(&APP_INFO, "tests/docs/prefs-entry").unwrap();
+    /// prefs.remove("theme.name");
+    /// prefs.entry("theme.name").or_insert("light").unwrap();
+    /// prefs.entry("theme.name").and_modify(|name| *name = "dark".into());
+    /// assert_eq!(prefs.get::<String>("theme.name").unwrap().unwrap(), "dark");
+    /// assert!(prefs.is_dirty());
+    /// prefs.save().unwrap();
+    /// ```
+    pub fn entry(&mut self, path: &str) -> PrefsEntry<'_> {
+        PrefsEntry {
+            prefs: self,
+            path: path.into(),
+        }
+    }
+
+    /// Returns the document, as last loaded or changed.
+    #[must_use]
+    pub const fn document(&self) -> &PrefsDocument {
+        &self.document
+    }
+
+    /// Returns the document, for changes that aren't saved until [`save`](#method.save) is
+    /// called. The handle counts as changed from then on.
+    pub fn document_mut(&mut self) -> &mut PrefsDocument {
+        self.dirty = true;
+        &mut self.document
+    }
+
+    /// Returns `true` if the document was changed since it was last loaded or saved.
+    #[must_use]
+    pub const fn is_dirty(&self) -> bool {
+        self.dirty
+    }
+
+    /// Saves the document.
+    ///
+    /// # Errors
+    /// If a serialization or file I/O error (e.g. permission denied) occurs. The handle still
+    /// counts as changed in that case.
+    pub fn save(&mut self) -> Result<(), PreferencesError> {
+        let data = serde_json::to_vec(&self.document)?;
+        self.backend
+            .write_at(&self.app, &self.path, &self.key, &data, None)?;
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Replaces the document with the saved data, discarding unsaved changes. A key without
+    /// saved data reloads as an empty document.
+    ///
+    /// # Errors
+    /// If the data exists but can't be read, or isn't a JSON object. The document is left
+    /// unchanged in that case.
+    pub fn reload(&mut self) -> Result<(), PreferencesError> {
+        self.document = match self.backend.read_at(&self.path, &self.key) {
+            Ok(data) => serde_json::from_slice(&data)?,
+            Err(e) if e.is_not_found() => PrefsDocument::new(),
+            Err(e) => return Err(e),
+        };
+        self.dirty = false;
+        Ok(())
+    }
+
+    /// Returns the key of the document.
+    #[must_use]
+    pub fn key(&self) -> &str {
+        &self.key
+    }
+
+    /// Returns the path of the file that stores the document.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Entry of a [`Prefs`](struct.Prefs.html) document, returned by
+/// [`Prefs::entry`](struct.Prefs.html#method.entry).
+///
+/// Like the entries of a `HashMap`, it gives mutable access to the value at a path, inserting a
+/// default value first if there is none. Since the value can then be changed through the
+/// returned reference, the handle counts as changed whenever one is handed out.
+#[derive(Debug)]
+pub struct PrefsEntry<'a> {
+    prefs: &'a mut Prefs,
+    path: String,
+}
+
+impl<'a> PrefsEntry<'a> {
+    /// Returns the path of this entry.
+    #[must_use]
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    /// Returns the value of this entry, if it exists.
+    #[must_use]
+    pub fn get(&self) -> Option<&Value> {
+        self.prefs.document.get_value(&self.path)
+    }
+
+    /// Inserts `default` if the entry doesn't exist, then returns its value.
+    ///
+    /// # Errors
+    /// If `default` can't be serialized. The document is left unchanged in that case.
+    pub fn or_insert<V: Serialize>(self, default: V) -> Result<&'a mut Value, PreferencesError> {
+        self.or_insert_with(|| default)
+    }
+
+    /// Inserts the value returned by `f` if the entry doesn't exist, then returns its value.
+    ///
+    /// # Errors
+    /// If the new value can't be serialized. The document is left unchanged in that case.
+    pub fn or_insert_with<V, F>(self, f: F) -> Result<&'a mut Value, PreferencesError>
+    where
+        V: Serialize,
+        F: FnOnce() -> V,
+    {
+        let default = match self.get() {
+            Some(_) => None,
+            None => Some(serde_json::to_value(f())?),
+        };
+        self.prefs.dirty = true;
+        let value = self.prefs.document.slot(&self.path);
+        if let Some(default) = default {
+            *value = default;
+        }
+        Ok(value)
+    }
+
+    /// Calls `f` with the value of the entry, if it exists.
+    #[must_use]
+    pub fn and_modify<F: FnOnce(&mut Value)>(self, f: F) -> Self {
+        if let Some(value) = self.prefs.document.get_value_mut(&self.path) {
+            f(value);
+            self.prefs.dirty = true;
+        }
+        self
+    }
+}
@@ -0,0 +1,24 @@
+//! The most commonly used items of this crate, for importing all at once.
+//!
+//! ```
+//! use preferences::prelude::*;
+//! const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+//!
+//! let mut prefs = PreferencesMap::<String>::new();
+//! prefs.set_bool("fullscreen", true);
+//! prefs.save(&APP_INFO, "tests/docs/prelude").unwrap();
+//! ```
+//!
+//! Besides the core types, this brings every trait of the crate into scope (along with the
+//! types and traits of enabled optional features), so their methods can be called without
+//! importing each one separately.
+
+pub use crate::{
+    AppInfo, Backend, FileBackend, Flush, Preferences, PreferencesError, PreferencesMap, Prefs,
+    PrefsDocument, TrustLevel, TrustStore, TypedPreferences,
+};
+
+#[cfg(feature = "indexmap")]
+pub use crate::OrderedPreferencesMap;
+#[cfg(feature = "async")]
+pub use crate::{AsyncBackend, AsyncPreferences};
@@ -0,0 +1,111 @@
+use crate::{Backend, Capabilities, FileBackend, PreferencesError};
+use app_dirs::AppInfo;
+
+/// Backend keeping a separate set of preferences for each named profile, such as "work" and
+/// "personal".
+///
+/// Each profile stores its keys in its own directory, next to the app's directory, with the same
+/// options as the [`FileBackend`](struct.FileBackend.html) it was created from. Saving or loading
+/// through a profile never touches the data of other profiles, or the data saved without one.
+/// Profile names can't be empty: a profile named `""` fails to save or load anything, with a
+/// [`PreferencesError::Io`](enum.PreferencesError.html) of kind `InvalidInput`.
+///
+/// Use [`FileBackend::profiles`](struct.FileBackend.html#method.profiles) to list the profiles of
+/// an app.
+///
+/// ```
+/// use preferences::{AppInfo, Preferences, Profile};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let key = "tests/docs/profile";
+/// let work = Profile::new("doc-work");
+/// work.delete(&APP_INFO).unwrap();
+/// String::from("dark").save_in(&work, &APP_INFO, key).unwrap();
+///
+/// let personal = work.copy_to(&APP_INFO, "doc-personal").unwrap();
+/// String::from("light").save_in(&personal, &APP_INFO, key).unwrap();
+/// assert_eq!(String::load_in(&work, &APP_INFO, key).unwrap(), "dark");
+/// # personal.delete(&APP_INFO).unwrap();
+/// # work.delete(&APP_INFO).unwrap();
+/// ```
+#[derive(Clone, Debug)]
+pub struct Profile {
+    backend: FileBackend,
+    name: String,
+}
+
+impl Profile {
+    /// Creates a backend for profile `name`, in the active user's configuration directory.
+    #[must_use]
+    pub fn new<S: Into<String>>(name: S) -> Self {
+        Self::with_backend(FileBackend::new(), name)
+    }
+
+    /// Creates a backend for profile `name`, with the location and options of `backend`.
+    #[must_use]
+    pub fn with_backend<S: Into<String>>(backend: FileBackend, name: S) -> Self {
+        Self {
+            backend,
+            name: name.into(),
+        }
+    }
+
+    /// Returns the name of this profile.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Copies all the data of this profile for `app` into a new profile `name`, and returns it.
+    ///
+    /// # Errors
+    /// Same as [`FileBackend::copy_profile`](struct.FileBackend.html#method.copy_profile).
+    pub fn copy_to<S: Into<String>>(
+        &self,
+        app: &AppInfo,
+        name: S,
+    ) -> Result<Self, PreferencesError> {
+        let name = name.into();
+        self.backend.copy_profile(app, &self.name, &name)?;
+        Ok(Self::with_backend(self.backend.clone(), name))
+    }
+
+    /// Renames this profile to `name`, along with all its data for `app`.
+    ///
+    /// # Errors
+    /// Same as [`FileBackend::rename_profile`](struct.FileBackend.html#method.rename_profile).
+    /// This profile keeps its name in that case.
+    pub fn rename<S: Into<String>>(
+        &mut self,
+        app: &AppInfo,
+        name: S,
+    ) -> Result<(), PreferencesError> {
+        let name = name.into();
+        self.backend.rename_profile(app, &self.name, &name)?;
+        self.name = name;
+        Ok(())
+    }
+
+    /// Deletes all the data of this profile for `app`. Deleting a profile without data isn't an
+    /// error.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs, or if this profile's name is empty.
+    pub fn delete(&self, app: &AppInfo) -> Result<(), PreferencesError> {
+        self.backend.remove_profile(app, &self.name)
+    }
+}
+
+impl Backend for Profile {
+    fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        self.backend.in_profile(app, &self.name)?.read(app, key)
+    }
+    fn write(&self, app: &AppInfo, key: &str, data: &[u8]) -> Result<(), PreferencesError> {
+        self.backend
+            .in_profile(app, &self.name)?
+            .write(app, key, data)
+    }
+    fn capabilities(&self) -> Capabilities {
+        self.backend.capabilities()
+    }
+}
@@ -0,0 +1,142 @@
+use crate::backend::write_atomic;
+use crate::{Backend, PreferencesError};
+use app_dirs::{sanitized, AppInfo};
+use std::env;
+use std::fmt;
+use std::fs::{create_dir_all, File};
+use std::io::{self, ErrorKind, Read};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+static PROJECT_FILE_EXTENSION: &str = ".json";
+static DEFAULT_PROJECT_FILENAME: &str = "prefs.json";
+
+type TrustCheck = Arc<dyn Fn(&Path) -> bool + Send + Sync>;
+
+/// Backend storing project-local preferences in a directory checked into a project.
+///
+/// Editors and build tools often let a project override some user preferences with a file such
+/// as `.myapp/settings.json`, found by walking up from the current directory. This backend maps
+/// each key to a `.json` file in such a directory, and is meant to be layered in front of the
+/// user's own preferences with a [`LayeredBackend`](struct.LayeredBackend.html).
+///
+/// Since project files may come from untrusted sources (e.g. a freshly cloned repository), a
+/// trust check can be installed with [`with_trust_check`](#method.with_trust_check). Data from
+/// an untrusted project is never read: it behaves as if the project had no data at all.
+///
+/// ```
+/// use preferences::{FileBackend, LayeredBackend, ProjectBackend};
+///
+/// let mut backend = LayeredBackend::new();
+/// if let Some(project) = ProjectBackend::discover(".myapp") {
+///     backend = backend.layer(project.with_trust_check(|dir| {
+///         // Ask the user whether to apply the settings in `dir`...
+///         # let _ = dir;
+///         true
+///     }));
+/// }
+/// let backend = backend.writable_layer(FileBackend::new());
+/// ```
+#[derive(Clone)]
+pub struct ProjectBackend {
+    dir: PathBuf,
+    trust_check: Option<TrustCheck>,
+}
+
+impl ProjectBackend {
+    /// Creates a backend storing files in `dir`.
+    #[must_use]
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        Self {
+            dir: dir.into(),
+            trust_check: None,
+        }
+    }
+
+    /// Looks for a directory named `dir_name` in the current directory or any of its ancestors,
+    /// returning a backend for the closest one found.
+    #[must_use]
+    pub fn discover<P: AsRef<Path>>(dir_name: P) -> Option<Self> {
+        Self::discover_from(env::current_dir().ok()?, dir_name)
+    }
+
+    /// Same as `discover`, but starts looking from `start` instead of the current directory.
+    #[must_use]
+    pub fn discover_from<P, Q>(start: P, dir_name: Q) -> Option<Self>
+    where
+        P: AsRef<Path>,
+        Q: AsRef<Path>,
+    {
+        start
+            .as_ref()
+            .ancestors()
+            .map(|dir| dir.join(dir_name.as_ref()))
+            .find(|dir| dir.is_dir())
+            .map(Self::new)
+    }
+
+    /// Only reads data from this project if `check` returns `true` for its directory.
+    ///
+    /// The check runs on every read, so it should remember the user's answer rather than
+    /// prompting each time.
+    #[must_use]
+    pub fn with_trust_check<F>(mut self, check: F) -> Self
+    where
+        F: Fn(&Path) -> bool + Send + Sync + 'static,
+    {
+        self.trust_check = Some(Arc::new(check));
+        self
+    }
+
+    /// Returns the directory this backend stores its files in.
+    #[must_use]
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+
+    /// Returns the path of the file that stores `key`. An empty key is stored in `prefs.json`.
+    #[must_use]
+    pub fn path(&self, key: &str) -> PathBuf {
+        let mut path = self.dir.clone();
+        path.extend(key.split('/').filter(|s| !s.is_empty()).map(sanitized));
+        if path == self.dir {
+            return path.join(DEFAULT_PROJECT_FILENAME);
+        }
+        let mut name = path.into_os_string();
+        name.push(PROJECT_FILE_EXTENSION);
+        name.into()
+    }
+
+    fn is_trusted(&self) -> bool {
+        self.trust_check
+            .as_ref()
+            .map_or(true, |check| check(&self.dir))
+    }
+}
+
+impl fmt::Debug for ProjectBackend {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProjectBackend")
+            .field("dir", &self.dir)
+            .field("trust_check", &self.trust_check.is_some())
+            .finish()
+    }
+}
+
+impl Backend for ProjectBackend {
+    fn read(&self, _app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        if !self.is_trusted() {
+            let msg = format!("{}: project is not trusted", self.dir.display());
+            return Err(io::Error::new(ErrorKind::NotFound, msg).into());
+        }
+        let mut data = Vec::new();
+        File::open(self.path(key))?.read_to_end(&mut data)?;
+        Ok(data)
+    }
+    fn write(&self, _app: &AppInfo, key: &str, data: &[u8]) -> Result<(), PreferencesError> {
+        let path = self.path(key);
+        path.parent().map(create_dir_all);
+        write_atomic(&path, data, 0, false)?;
+        Ok(())
+    }
+}
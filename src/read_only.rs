@@ -0,0 +1,44 @@
+use crate::{Backend, Capabilities, Consistency, PreferencesError};
+use app_dirs::AppInfo;
+
+/// Wrapper making any backend read-only.
+///
+/// Loading goes through to the wrapped backend as usual, but saving always fails with
+/// [`PreferencesError::ReadOnly`](enum.PreferencesError.html#variant.ReadOnly). This is handy
+/// for kiosk deployments, or for admin-provisioned defaults that users must not overwrite.
+#[derive(Clone, Debug, Default)]
+pub struct ReadOnly<B> {
+    inner: B,
+}
+
+impl<B: Backend> ReadOnly<B> {
+    /// Wraps `backend`, rejecting all writes to it.
+    #[must_use]
+    pub const fn new(backend: B) -> Self {
+        Self { inner: backend }
+    }
+
+    /// Unwraps this wrapper, returning the underlying backend.
+    #[must_use]
+    pub fn into_inner(self) -> B {
+        self.inner
+    }
+}
+
+impl<B: Backend> Backend for ReadOnly<B> {
+    fn read(&self, app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+        self.inner.read(app, key)
+    }
+    fn write(&self, _app: &AppInfo, key: &str, _data: &[u8]) -> Result<(), PreferencesError> {
+        Err(PreferencesError::ReadOnly(key.into()))
+    }
+    fn capabilities(&self) -> Capabilities {
+        // Every write is refused, so there are no writes to make atomic or transactional.
+        Capabilities {
+            atomic: false,
+            transactional: false,
+            consistency: Consistency::Unspecified,
+            ..self.inner.capabilities()
+        }
+    }
+}
@@ -0,0 +1,110 @@
+use crate::{FileBackend, Preferences, PreferencesError, PrefsWatcher};
+use app_dirs::AppInfo;
+use std::fmt;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
+
+struct State<T> {
+    value: Arc<T>,
+    error: Option<PreferencesError>,
+    reloaded: bool,
+}
+
+/// Wrapper holding the latest version of some user data, reloaded automatically whenever its
+/// file changes. (Requires the `watch` feature.)
+///
+/// This gives long-running programs hot-reloading of their settings: [`get`](#method.get)
+/// returns a snapshot of the current value, which stays valid (and unchanged) however long it's
+/// kept, while newer versions are swapped in as the file changes. If a new version fails to
+/// load, the previous value is kept and the error can be retrieved with
+/// [`take_error`](#method.take_error).
+///
+/// ```no_run
+/// use preferences::{AppInfo, PreferencesMap, Reloading};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let config = Reloading::<PreferencesMap>::new(&APP_INFO, "daemon/config").unwrap();
+/// loop {
+///     let snapshot = config.get();
+///     println!("Serving with {:?}", snapshot.get("workers"));
+///     # break;
+/// }
+/// ```
+pub struct Reloading<T> {
+    state: Arc<Mutex<State<T>>>,
+    watcher: PrefsWatcher,
+}
+
+impl<T: Preferences + Send + Sync + 'static> Reloading<T> {
+    /// Loads the user data saved under `key`, and starts watching it.
+    ///
+    /// # Errors
+    /// If the data can't be loaded, or if the operating system refuses to watch its file.
+    pub fn new<S: AsRef<str>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError> {
+        Self::with_backend(&FileBackend::new(), app, key)
+    }
+
+    /// Same as `new`, but loads the data saved in `backend`.
+    ///
+    /// # Errors
+    /// If the data can't be loaded, or if the operating system refuses to watch its file.
+    pub fn with_backend<S: AsRef<str>>(
+        backend: &FileBackend,
+        app: &AppInfo,
+        key: S,
+    ) -> Result<Self, PreferencesError> {
+        let key = key.as_ref();
+        let state = Arc::new(Mutex::new(State {
+            value: Arc::new(T::load_in(backend, app, key)?),
+            error: None,
+            reloaded: false,
+        }));
+        let watcher = {
+            let state = Arc::clone(&state);
+            PrefsWatcher::with_backend(backend, app, key, move |result| {
+                let mut state = lock(&state);
+                match result {
+                    Ok(value) => state.value = Arc::new(value),
+                    Err(e) => state.error = Some(e),
+                }
+                state.reloaded = true;
+                drop(state);
+            })?
+        };
+        // The data may have changed before the watcher started. If it changed after, the
+        // watcher has it covered, with a version at least as new as this one.
+        let value = T::load_in(backend, app, key)?;
+        let mut current = lock(&state);
+        if !current.reloaded {
+            current.value = Arc::new(value);
+        }
+        drop(current);
+        Ok(Self { state, watcher })
+    }
+}
+
+impl<T> Reloading<T> {
+    /// Returns the latest successfully loaded value.
+    #[must_use]
+    pub fn get(&self) -> Arc<T> {
+        Arc::clone(&lock(&self.state).value)
+    }
+
+    /// Returns and clears the last error that occurred while reloading, if any.
+    #[must_use]
+    pub fn take_error(&self) -> Option<PreferencesError> {
+        lock(&self.state).error.take()
+    }
+}
+
+impl<T: fmt::Debug> fmt::Debug for Reloading<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Reloading")
+            .field("value", &lock(&self.state).value)
+            .field("path", &self.watcher.path())
+            .finish_non_exhaustive()
+    }
+}
+
+fn lock<T>(state: &Mutex<State<T>>) -> MutexGuard<'_, State<T>> {
+    state.lock().unwrap_or_else(PoisonError::into_inner)
+}
@@ -0,0 +1,158 @@
+use crate::{Backend, FileBackend, Flush, Preferences, PreferencesError};
+use app_dirs::AppInfo;
+use std::io::{self, ErrorKind};
+use std::sync::mpsc::{channel, Receiver, Sender};
+use std::thread::{self, JoinHandle};
+
+enum Job {
+    Save {
+        app: AppInfo,
+        key: String,
+        data: Vec<u8>,
+    },
+    Flush(Sender<Option<PreferencesError>>),
+}
+
+/// Handle that saves user data on a background thread.
+///
+/// Saving is fast from the caller's point of view: [`save`](#method.save) only serializes the
+/// value, and the actual write happens later on a worker thread. This keeps frequent saves (e.g.
+/// on every tick of a slider) from causing hitches in a UI thread.
+///
+/// Saves are written in the order they were queued. When several saves of the same key are
+/// waiting, only the most recent one is written. Call [`flush`](#method.flush) to wait for all
+/// queued saves and find out whether they succeeded; dropping the saver also waits for them,
+/// but ignores errors.
+///
+/// ```
+/// use preferences::{AppInfo, Preferences, PrefsSaver};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let saver = PrefsSaver::new();
+/// for volume in 0..100 {
+///     saver.save(&volume, &APP_INFO, "tests/docs/saver").unwrap();
+/// }
+/// saver.flush().unwrap();
+/// assert_eq!(i32::load(&APP_INFO, "tests/docs/saver").unwrap(), 99);
+/// ```
+#[derive(Debug)]
+pub struct PrefsSaver {
+    sender: Option<Sender<Job>>,
+    worker: Option<JoinHandle<()>>,
+}
+
+impl PrefsSaver {
+    /// Creates a saver writing to the default [`FileBackend`](struct.FileBackend.html).
+    #[must_use]
+    pub fn new() -> Self {
+        Self::with_backend(FileBackend::new())
+    }
+
+    /// Creates a saver writing to `backend`.
+    #[must_use]
+    pub fn with_backend<B: Backend + Send + 'static>(backend: B) -> Self {
+        let (sender, receiver) = channel();
+        let worker = thread::spawn(move || run(&backend, &receiver));
+        Self {
+            sender: Some(sender),
+            worker: Some(worker),
+        }
+    }
+
+    /// Queues `value` to be saved under `key`.
+    ///
+    /// The value is serialized immediately, so later changes to it don't affect what is saved.
+    ///
+    /// # Errors
+    /// If a serialization error occurs, or if the worker thread is gone (which only happens if
+    /// the backend panicked).
+    pub fn save<T, S>(&self, value: &T, app: &AppInfo, key: S) -> Result<(), PreferencesError>
+    where
+        T: Preferences,
+        S: AsRef<str>,
+    {
+        self.send(Job::Save {
+            app: app.clone(),
+            key: key.as_ref().into(),
+            data: value.serialized()?,
+        })
+    }
+
+    /// Waits until every save queued so far has been written.
+    ///
+    /// # Errors
+    /// If any save since the last flush failed, returns the first error encountered.
+    pub fn flush(&self) -> Result<(), PreferencesError> {
+        let (sender, receiver) = channel();
+        self.send(Job::Flush(sender))?;
+        receiver
+            .recv()
+            .map_err(|_| worker_gone())?
+            .map_or(Ok(()), Err)
+    }
+
+    fn send(&self, job: Job) -> Result<(), PreferencesError> {
+        let sender = self.sender.as_ref().ok_or_else(worker_gone)?;
+        sender.send(job).map_err(|_| worker_gone())
+    }
+}
+
+impl Flush for PrefsSaver {
+    fn flush(&self) -> Result<(), PreferencesError> {
+        Self::flush(self)
+    }
+}
+
+impl Default for PrefsSaver {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for PrefsSaver {
+    fn drop(&mut self) {
+        drop(self.sender.take());
+        if let Some(worker) = self.worker.take() {
+            let _ = worker.join();
+        }
+    }
+}
+
+fn worker_gone() -> PreferencesError {
+    io::Error::new(ErrorKind::BrokenPipe, "preferences saver thread stopped").into()
+}
+
+fn run<B: Backend>(backend: &B, receiver: &Receiver<Job>) {
+    let mut error = None;
+    while let Ok(job) = receiver.recv() {
+        let mut batch = vec![job];
+        batch.extend(receiver.try_iter());
+        for (index, job) in batch.iter().enumerate() {
+            match *job {
+                Job::Save {
+                    ref app,
+                    ref key,
+                    ref data,
+                } => {
+                    if is_superseded(&batch[index + 1..], app, key) {
+                        continue;
+                    }
+                    if let Err(e) = backend.write(app, key, data) {
+                        error.get_or_insert(e);
+                    }
+                }
+                Job::Flush(ref reply) => {
+                    let _ = reply.send(error.take());
+                }
+            }
+        }
+    }
+}
+
+/// Returns `true` if one of the `later` jobs saves the same key before the next flush.
+fn is_superseded(later: &[Job], app: &AppInfo, key: &str) -> bool {
+    later
+        .iter()
+        .take_while(|job| matches!(**job, Job::Save { .. }))
+        .any(|job| matches!(*job, Job::Save { app: ref a, key: ref k, .. } if a == app && k == key))
+}
@@ -0,0 +1,63 @@
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use std::fmt::{self, Write};
+use std::sync::Arc;
+
+type HmacSha256 = Hmac<Sha256>;
+
+/// Secret used to sign saved files. Its `Debug` output doesn't reveal it.
+#[derive(Clone)]
+pub struct SigningKey(Arc<[u8]>);
+
+impl SigningKey {
+    pub fn new(secret: &[u8]) -> Self {
+        Self(secret.into())
+    }
+
+    /// Returns the hex-encoded HMAC-SHA256 of `data` saved under `key`.
+    ///
+    /// The key is part of the signed message, so a signed file can't be passed off as the data
+    /// of another key.
+    pub fn sign(&self, key: &str, data: &[u8]) -> String {
+        self.mac(key, data)
+            .finalize()
+            .into_bytes()
+            .iter()
+            .fold(String::new(), |mut hex, byte| {
+                let _ = write!(hex, "{byte:02x}");
+                hex
+            })
+    }
+
+    /// Returns `true` if `signature` is the signature of `data` saved under `key`.
+    pub fn verify(&self, key: &str, data: &[u8], signature: &str) -> bool {
+        decode_hex(signature).map_or(false, |bytes| {
+            self.mac(key, data).verify_slice(&bytes).is_ok()
+        })
+    }
+
+    fn mac(&self, key: &str, data: &[u8]) -> HmacSha256 {
+        // HMAC accepts keys of any length.
+        let mut mac = HmacSha256::new_from_slice(&self.0).expect("HMAC key of any length");
+        mac.update(key.as_bytes());
+        mac.update(&[0]);
+        mac.update(data);
+        mac
+    }
+}
+
+impl fmt::Debug for SigningKey {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("SigningKey(..)")
+    }
+}
+
+fn decode_hex(hex: &str) -> Option<Vec<u8>> {
+    if hex.len() % 2 != 0 {
+        return None;
+    }
+    (0..hex.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(hex.get(i..i + 2)?, 16).ok())
+        .collect()
+}
@@ -0,0 +1,173 @@
+use crate::{Backend, FileBackend, PreferencesError};
+use app_dirs::AppInfo;
+use std::io::{self, ErrorKind};
+
+/// Configuration source reading the data saved under one preferences key, for use in a `figment`
+/// or `config` pipeline.
+///
+/// The saved data must be a JSON object, whose fields become configuration values. A key without
+/// saved data provides no values, so preferences the user never saved don't stop the pipeline.
+///
+/// With the `figment` feature, this is a `figment::Provider` (for the default profile). With the
+/// `config` feature, it's a `config::Source`.
+///
+/// (Requires the `figment` or `config` feature.)
+///
+/// ```
+/// # #[cfg(feature = "figment")] {
+/// use figment::Figment;
+/// use figment::providers::Serialized;
+/// use preferences::{AppInfo, Preferences, PrefsSource};
+/// use serde_json::json;
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// json!({"theme": "dark"}).save(&APP_INFO, "tests/docs/source").unwrap();
+/// let config = Figment::new()
+///     .merge(Serialized::defaults(json!({"theme": "light", "volume": 5})))
+///     .merge(PrefsSource::new(&APP_INFO, "tests/docs/source"));
+/// assert_eq!(config.extract_inner::<String>("theme").unwrap(), "dark");
+/// assert_eq!(config.extract_inner::<u32>("volume").unwrap(), 5);
+/// # }
+/// ```
+#[derive(Clone, Debug)]
+pub struct PrefsSource<B = FileBackend> {
+    backend: B,
+    app: AppInfo,
+    key: String,
+}
+
+impl PrefsSource {
+    /// Creates a source reading `key` from the default [`FileBackend`](struct.FileBackend.html).
+    #[must_use]
+    pub fn new<S: Into<String>>(app: &AppInfo, key: S) -> Self {
+        Self::with_backend(FileBackend::new(), app, key)
+    }
+}
+
+impl<B: Backend> PrefsSource<B> {
+    /// Creates a source reading `key` from `backend`.
+    #[must_use]
+    pub fn with_backend<S: Into<String>>(backend: B, app: &AppInfo, key: S) -> Self {
+        Self {
+            backend,
+            app: app.clone(),
+            key: key.into(),
+        }
+    }
+
+    /// Returns the saved data, or `None` if there is none.
+    fn read(&self) -> Result<Option<Vec<u8>>, PreferencesError> {
+        match self.backend.read(&self.app, &self.key) {
+            Ok(data) => Ok(Some(data)),
+            Err(e) if e.is_not_found() => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+}
+
+fn not_found(key: &str) -> PreferencesError {
+    io::Error::new(
+        ErrorKind::NotFound,
+        format!("{key}: not found in configuration"),
+    )
+    .into()
+}
+
+fn invalid_data<E: ToString>(key: &str, error: &E) -> PreferencesError {
+    let msg = format!("{key}: {}", error.to_string());
+    io::Error::new(ErrorKind::InvalidData, msg).into()
+}
+
+#[cfg(feature = "figment")]
+mod figment_impls {
+    use super::{invalid_data, not_found, PrefsSource};
+    use crate::{Backend, PreferencesError};
+    use app_dirs::AppInfo;
+    use figment::value::{Dict, Map};
+    use figment::{Error, Figment, Metadata, Profile, Provider};
+
+    impl<B: Backend> Provider for PrefsSource<B> {
+        fn metadata(&self) -> Metadata {
+            Metadata::named(format!("preferences key {:?}", self.key))
+        }
+
+        fn data(&self) -> Result<Map<Profile, Dict>, Error> {
+            let dict = match self.read().map_err(|e| e.to_string())? {
+                Some(data) => serde_json::from_slice(&data).map_err(|e| e.to_string())?,
+                None => Dict::new(),
+            };
+            Ok(Profile::Default.collect(dict))
+        }
+    }
+
+    /// Reads the value at the path made of the key's components, ignoring the app. Writing fails
+    /// with [`PreferencesError::ReadOnly`](enum.PreferencesError.html#variant.ReadOnly).
+    ///
+    /// (Requires the `figment` feature.)
+    impl Backend for Figment {
+        fn read(&self, _app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+            let path = key.split('/').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+            let value = if path.is_empty() {
+                self.extract::<serde_json::Value>()
+            } else {
+                self.extract_inner::<serde_json::Value>(&path.join("."))
+            };
+            match value {
+                Ok(value) => Ok(serde_json::to_vec(&value)?),
+                Err(e) if e.missing() => Err(not_found(key)),
+                Err(e) => Err(invalid_data(key, &e)),
+            }
+        }
+        fn write(&self, _app: &AppInfo, key: &str, _data: &[u8]) -> Result<(), PreferencesError> {
+            Err(PreferencesError::ReadOnly(key.into()))
+        }
+    }
+}
+
+#[cfg(feature = "config")]
+mod config_impls {
+    use super::{invalid_data, not_found, PrefsSource};
+    use crate::{Backend, PreferencesError};
+    use app_dirs::AppInfo;
+    use config::{Config, ConfigError, Map, Source, Value};
+
+    impl<B> Source for PrefsSource<B>
+    where
+        B: Backend + Clone + std::fmt::Debug + Send + Sync + 'static,
+    {
+        fn clone_into_box(&self) -> Box<dyn Source + Send + Sync> {
+            Box::new(self.clone())
+        }
+
+        fn collect(&self) -> Result<Map<String, Value>, ConfigError> {
+            let data = self.read().map_err(|e| ConfigError::Foreign(Box::new(e)))?;
+            data.map_or_else(
+                || Ok(Map::new()),
+                |data| serde_json::from_slice(&data).map_err(|e| ConfigError::Foreign(Box::new(e))),
+            )
+        }
+    }
+
+    /// Reads the value at the path made of the key's components, ignoring the app. Writing fails
+    /// with [`PreferencesError::ReadOnly`](enum.PreferencesError.html#variant.ReadOnly).
+    ///
+    /// (Requires the `config` feature.)
+    impl Backend for Config {
+        fn read(&self, _app: &AppInfo, key: &str) -> Result<Vec<u8>, PreferencesError> {
+            let path = key.split('/').filter(|s| !s.is_empty()).collect::<Vec<_>>();
+            let value = if path.is_empty() {
+                self.clone().try_deserialize::<serde_json::Value>()
+            } else {
+                self.get::<serde_json::Value>(&path.join("."))
+            };
+            match value {
+                Ok(value) => Ok(serde_json::to_vec(&value)?),
+                Err(ConfigError::NotFound(_)) => Err(not_found(key)),
+                Err(e) => Err(invalid_data(key, &e)),
+            }
+        }
+        fn write(&self, _app: &AppInfo, key: &str, _data: &[u8]) -> Result<(), PreferencesError> {
+            Err(PreferencesError::ReadOnly(key.into()))
+        }
+    }
+}
@@ -0,0 +1,93 @@
+use crate::{FileBackend, Preferences, PreferencesError};
+use app_dirs::AppInfo;
+
+/// Set of saves and deletions applied to several keys as a single all-or-nothing operation.
+///
+/// Changes are staged in memory, and nothing is written until [`commit`](#method.commit).
+/// Dropping a transaction without committing it discards every staged change. Committing goes
+/// through the journal of [`FileBackend::write_journaled`](struct.FileBackend.html#method.write_journaled),
+/// so even a crash in the middle of a commit can't leave some keys updated and others not: call
+/// [`FileBackend::recover`](struct.FileBackend.html#method.recover) on startup to finish an
+/// interrupted commit.
+///
+/// ```
+/// use preferences::{AppInfo, Preferences, Transaction};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let mut transaction = Transaction::new(&APP_INFO);
+/// transaction.save(&String::from("alice"), "tests/docs/transaction/profile").unwrap();
+/// transaction.save(&11, "tests/docs/transaction/volume").unwrap();
+/// transaction.delete("tests/docs/transaction/window");
+/// transaction.commit().unwrap();
+/// assert_eq!(i32::load(&APP_INFO, "tests/docs/transaction/volume").unwrap(), 11);
+/// ```
+#[derive(Debug)]
+pub struct Transaction {
+    backend: FileBackend,
+    app: AppInfo,
+    changes: Vec<(String, Option<Vec<u8>>)>,
+}
+
+impl Transaction {
+    /// Starts a transaction on the default [`FileBackend`](struct.FileBackend.html).
+    #[must_use]
+    pub fn new(app: &AppInfo) -> Self {
+        Self::with_backend(FileBackend::new(), app)
+    }
+
+    /// Starts a transaction on `backend`.
+    #[must_use]
+    pub fn with_backend(backend: FileBackend, app: &AppInfo) -> Self {
+        Self {
+            backend,
+            app: app.clone(),
+            changes: Vec::new(),
+        }
+    }
+
+    /// Stages saving `value` under `key`. The value is serialized immediately, so later changes
+    /// to it don't affect what is saved.
+    ///
+    /// # Errors
+    /// If a serialization error occurs. The transaction can still be committed without this
+    /// change.
+    pub fn save<T, S>(&mut self, value: &T, key: S) -> Result<&mut Self, PreferencesError>
+    where
+        T: Preferences,
+        S: AsRef<str>,
+    {
+        let data = value.serialized()?;
+        self.stage(key.as_ref(), Some(data));
+        Ok(self)
+    }
+
+    /// Stages deleting the data saved under `key`.
+    pub fn delete<S: AsRef<str>>(&mut self, key: S) -> &mut Self {
+        self.stage(key.as_ref(), None);
+        self
+    }
+
+    /// Returns `true` if no changes are staged.
+    #[must_use]
+    pub fn is_empty(&self) -> bool {
+        self.changes.is_empty()
+    }
+
+    /// Applies every staged change.
+    ///
+    /// # Errors
+    /// If a file I/O error (e.g. permission denied) occurs. The changes already applied are then
+    /// rolled back, as far as possible.
+    pub fn commit(self) -> Result<(), PreferencesError> {
+        if self.changes.is_empty() {
+            return Ok(());
+        }
+        self.backend.commit_journal(&self.app, self.changes)
+    }
+
+    /// Stages a change, replacing any earlier change to the same key.
+    fn stage(&mut self, key: &str, data: Option<Vec<u8>>) {
+        self.changes.retain(|(staged, _)| staged != key);
+        self.changes.push((key.into(), data));
+    }
+}
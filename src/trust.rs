@@ -0,0 +1,110 @@
+use crate::{Preferences, PreferencesError, PreferencesMap};
+use app_dirs::AppInfo;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+static DEFAULT_TRUST_STORE_KEY: &str = "trust-store";
+
+/// Whether the user approved applying the files found in a directory.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum TrustLevel {
+    /// The user approved the directory.
+    Trusted,
+    /// The user rejected the directory.
+    Untrusted,
+}
+
+/// Persistent record of which directories the user trusts.
+///
+/// This remembers the user's answer to "apply the settings found in this project?" prompts, for
+/// use with e.g. [`ProjectBackend::with_trust_check`](struct.ProjectBackend.html#method.with_trust_check).
+/// The record itself is saved as regular user data. Paths are canonicalized when possible, so
+/// different spellings of the same directory share one entry.
+///
+/// ```
+/// use preferences::{AppInfo, TrustLevel, TrustStore};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let mut store = TrustStore::load_key(&APP_INFO, "tests/docs/trust-store").unwrap();
+/// let project = std::env::temp_dir();
+/// if store.trust_level(&project).is_none() {
+///     // Ask the user...
+///     store.set_trust(&project, TrustLevel::Trusted).unwrap();
+/// }
+/// assert!(store.is_trusted(&project));
+/// ```
+#[derive(Clone, Debug)]
+pub struct TrustStore {
+    app: AppInfo,
+    key: String,
+    entries: PreferencesMap<TrustLevel>,
+}
+
+impl TrustStore {
+    /// Loads the trust store of `app`, or an empty one if none was saved yet.
+    ///
+    /// # Errors
+    /// If the saved trust store exists but can't be read.
+    pub fn load(app: &AppInfo) -> Result<Self, PreferencesError> {
+        Self::load_key(app, DEFAULT_TRUST_STORE_KEY)
+    }
+
+    /// Same as `load`, but uses a custom preferences key to store the trust store.
+    ///
+    /// # Errors
+    /// If the saved trust store exists but can't be read.
+    pub fn load_key<S: AsRef<str>>(app: &AppInfo, key: S) -> Result<Self, PreferencesError> {
+        let entries = match PreferencesMap::load(app, key.as_ref()) {
+            Err(e) if e.is_not_found() => PreferencesMap::new(),
+            result => result?,
+        };
+        Ok(Self {
+            app: app.clone(),
+            key: key.as_ref().into(),
+            entries,
+        })
+    }
+
+    /// Returns the recorded trust level of `dir`, or `None` if the user was never asked.
+    #[must_use]
+    pub fn trust_level<P: AsRef<Path>>(&self, dir: P) -> Option<TrustLevel> {
+        self.entries.get(&entry_key(dir.as_ref())).copied()
+    }
+
+    /// Returns `true` if `dir` was recorded as trusted.
+    #[must_use]
+    pub fn is_trusted<P: AsRef<Path>>(&self, dir: P) -> bool {
+        self.trust_level(dir) == Some(TrustLevel::Trusted)
+    }
+
+    /// Records the trust level of `dir`, and saves the trust store.
+    ///
+    /// # Errors
+    /// If the trust store can't be saved.
+    pub fn set_trust<P: AsRef<Path>>(
+        &mut self,
+        dir: P,
+        level: TrustLevel,
+    ) -> Result<(), PreferencesError> {
+        self.entries.insert(entry_key(dir.as_ref()), level);
+        self.entries.save(&self.app, &self.key)
+    }
+
+    /// Forgets the trust level of `dir`, so the user will be asked again, and saves the trust
+    /// store.
+    ///
+    /// # Errors
+    /// If the trust store can't be saved.
+    pub fn forget<P: AsRef<Path>>(&mut self, dir: P) -> Result<(), PreferencesError> {
+        self.entries.remove(&entry_key(dir.as_ref()));
+        self.entries.save(&self.app, &self.key)
+    }
+}
+
+fn entry_key(dir: &Path) -> String {
+    dir.canonicalize()
+        .unwrap_or_else(|_| dir.to_owned())
+        .to_string_lossy()
+        .into_owned()
+}
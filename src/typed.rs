@@ -0,0 +1,163 @@
+use crate::PreferencesMap;
+use std::any::type_name;
+use std::error::Error;
+use std::fmt::{self, Display};
+use std::str::FromStr;
+
+/// Error returned when an entry of a string map can't be parsed as the requested type.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct ParseValueError {
+    name: String,
+    value: String,
+    expected: &'static str,
+}
+
+impl ParseValueError {
+    /// Returns the name of the entry.
+    #[must_use]
+    pub fn name(&self) -> &str {
+        &self.name
+    }
+
+    /// Returns the value that couldn't be parsed.
+    #[must_use]
+    pub fn value(&self) -> &str {
+        &self.value
+    }
+
+    /// Returns the name of the type the value was parsed as.
+    #[must_use]
+    pub const fn expected(&self) -> &'static str {
+        self.expected
+    }
+}
+
+impl fmt::Display for ParseValueError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{}: {:?} is not a valid {}",
+            self.name, self.value, self.expected
+        )
+    }
+}
+
+impl Error for ParseValueError {}
+
+/// Typed accessors for maps of strings, such as `PreferencesMap<String>`.
+///
+/// Each `get_*` method comes in two variants. The strict one returns `Ok(None)` for a missing
+/// entry, and a [`ParseValueError`](struct.ParseValueError.html) for an entry that doesn't parse
+/// as the requested type. The lossy one, ending in `_or`, returns a default value in both cases.
+///
+/// ```
+/// use preferences::{PreferencesMap, TypedPreferences};
+///
+/// let mut prefs = PreferencesMap::<String>::new();
+/// prefs.set_bool("fullscreen", true);
+/// prefs.set_i64("volume", 7);
+/// prefs.insert("scale".into(), "large".into());
+///
+/// assert_eq!(prefs.get_bool("fullscreen"), Ok(Some(true)));
+/// assert_eq!(prefs.get_i64_or("volume", 5), 7);
+/// assert_eq!(prefs.get_i64("missing"), Ok(None));
+/// assert!(prefs.get_f64("scale").is_err());
+/// assert_eq!(prefs.get_f64_or("scale", 1.0), 1.0);
+/// ```
+pub trait TypedPreferences {
+    /// Returns the entry `name` as a string, if it exists.
+    fn get_str(&self, name: &str) -> Option<&str>;
+    /// Sets the entry `name` to `value`.
+    fn set_str(&mut self, name: &str, value: String);
+
+    /// Parses the entry `name` as a `T`.
+    ///
+    /// # Errors
+    /// If the entry exists but doesn't parse as a `T`.
+    fn get_parsed<T: FromStr>(&self, name: &str) -> Result<Option<T>, ParseValueError> {
+        self.get_str(name)
+            .map(|value| {
+                value.parse().map_err(|_| ParseValueError {
+                    name: name.into(),
+                    value: value.into(),
+                    expected: type_name::<T>(),
+                })
+            })
+            .transpose()
+    }
+    /// Parses the entry `name` as a `T`, returning `default` if it's missing or doesn't parse.
+    fn get_parsed_or<T: FromStr>(&self, name: &str, default: T) -> T {
+        self.get_parsed(name).ok().flatten().unwrap_or(default)
+    }
+    /// Sets the entry `name` to `value`, formatted so that `get_parsed` can read it back.
+    fn set_display<T: Display>(&mut self, name: &str, value: T) {
+        self.set_str(name, value.to_string());
+    }
+
+    /// Parses the entry `name` as a `bool` (`true` or `false`).
+    ///
+    /// # Errors
+    /// If the entry exists but isn't a `bool`.
+    fn get_bool(&self, name: &str) -> Result<Option<bool>, ParseValueError> {
+        self.get_parsed(name)
+    }
+    /// Parses the entry `name` as a `bool`, returning `default` if it's missing or invalid.
+    fn get_bool_or(&self, name: &str, default: bool) -> bool {
+        self.get_parsed_or(name, default)
+    }
+    /// Sets the entry `name` to a `bool`.
+    fn set_bool(&mut self, name: &str, value: bool) {
+        self.set_display(name, value);
+    }
+
+    /// Parses the entry `name` as an `i64`.
+    ///
+    /// # Errors
+    /// If the entry exists but isn't an `i64`.
+    fn get_i64(&self, name: &str) -> Result<Option<i64>, ParseValueError> {
+        self.get_parsed(name)
+    }
+    /// Parses the entry `name` as an `i64`, returning `default` if it's missing or invalid.
+    fn get_i64_or(&self, name: &str, default: i64) -> i64 {
+        self.get_parsed_or(name, default)
+    }
+    /// Sets the entry `name` to an `i64`.
+    fn set_i64(&mut self, name: &str, value: i64) {
+        self.set_display(name, value);
+    }
+
+    /// Parses the entry `name` as an `f64`.
+    ///
+    /// # Errors
+    /// If the entry exists but isn't an `f64`.
+    fn get_f64(&self, name: &str) -> Result<Option<f64>, ParseValueError> {
+        self.get_parsed(name)
+    }
+    /// Parses the entry `name` as an `f64`, returning `default` if it's missing or invalid.
+    fn get_f64_or(&self, name: &str, default: f64) -> f64 {
+        self.get_parsed_or(name, default)
+    }
+    /// Sets the entry `name` to an `f64`.
+    fn set_f64(&mut self, name: &str, value: f64) {
+        self.set_display(name, value);
+    }
+}
+
+impl TypedPreferences for PreferencesMap<String> {
+    fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name).map(String::as_str)
+    }
+    fn set_str(&mut self, name: &str, value: String) {
+        self.insert(name.into(), value);
+    }
+}
+
+#[cfg(feature = "indexmap")]
+impl TypedPreferences for crate::OrderedPreferencesMap<String> {
+    fn get_str(&self, name: &str) -> Option<&str> {
+        self.get(name).map(String::as_str)
+    }
+    fn set_str(&mut self, name: &str, value: String) {
+        self.insert(name.into(), value);
+    }
+}
@@ -0,0 +1,148 @@
+use crate::{FileBackend, Preferences, PreferencesError};
+use app_dirs::AppInfo;
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::fmt;
+use std::fs::create_dir_all;
+use std::io::{self, ErrorKind};
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError};
+use std::thread;
+use std::time::Duration;
+
+/// How long a file must stay untouched before a change is reported, so that a file being
+/// rewritten in several steps (e.g. by an editor) is only loaded once it's complete.
+const SETTLE_TIME: Duration = Duration::from_millis(50);
+
+/// Calls `callback` whenever the user data saved under `key` changes on disk. (Requires the
+/// `watch` feature.)
+///
+/// Changes are reported whether they come from this process, another one, or a hand-edit. The
+/// callback receives the freshly loaded value, or the error that occurred while loading it
+/// (e.g. "not found" if the file was removed). It runs on a background thread, until the
+/// returned watcher is dropped.
+///
+/// Unlike [`ChangeStream`](struct.ChangeStream.html), this relies on the operating system's
+/// file notifications (through the `notify` crate) rather than polling, so changes are noticed
+/// right away without any work in between.
+///
+/// ```no_run
+/// use preferences::{AppInfo, PreferencesMap};
+/// const APP_INFO: AppInfo = AppInfo{name: "preferences", author: "Rust language community"};
+///
+/// let _watcher = preferences::watch(&APP_INFO, "options/graphics", |change| match change {
+///     Ok::<PreferencesMap, _>(prefs) => println!("Graphics options are now {:?}", prefs),
+///     Err(e) => println!("Couldn't reload graphics options: {}", e),
+/// })
+/// .unwrap();
+/// ```
+///
+/// # Errors
+/// If the location of the preferences file can't be determined, or if the operating system
+/// refuses to watch it.
+pub fn watch<T, S, F>(app: &AppInfo, key: S, callback: F) -> Result<PrefsWatcher, PreferencesError>
+where
+    T: Preferences,
+    S: AsRef<str>,
+    F: FnMut(Result<T, PreferencesError>) + Send + 'static,
+{
+    PrefsWatcher::with_backend(&FileBackend::new(), app, key, callback)
+}
+
+/// Handle returned by [`watch`](fn.watch.html), which stops watching when dropped. (Requires the
+/// `watch` feature.)
+pub struct PrefsWatcher {
+    path: PathBuf,
+    _watcher: RecommendedWatcher,
+}
+
+impl PrefsWatcher {
+    /// Same as [`watch`](fn.watch.html), but watches the data saved in `backend`.
+    ///
+    /// # Errors
+    /// If the location of the preferences file can't be determined, or if the operating system
+    /// refuses to watch it.
+    pub fn with_backend<T, S, F>(
+        backend: &FileBackend,
+        app: &AppInfo,
+        key: S,
+        callback: F,
+    ) -> Result<Self, PreferencesError>
+    where
+        T: Preferences,
+        S: AsRef<str>,
+        F: FnMut(Result<T, PreferencesError>) + Send + 'static,
+    {
+        let path = backend.path(app, key.as_ref())?;
+        // Saves replace the file rather than modifying it, so watch its directory instead.
+        let dir = path.parent().map_or_else(PathBuf::new, Path::to_owned);
+        create_dir_all(&dir)?;
+        let (sender, receiver) = channel();
+        let file = path.clone();
+        let mut watcher = notify::recommended_watcher(move |event: notify::Result<Event>| {
+            if event
+                .as_ref()
+                .map_or(true, |event| event.paths.contains(&file))
+            {
+                let _ = sender.send(event.map(drop).map_err(into_io));
+            }
+        })
+        .map_err(into_io)?;
+        watcher
+            .watch(&dir, RecursiveMode::NonRecursive)
+            .map_err(into_io)?;
+        let reload = {
+            let backend = backend.clone();
+            let app = app.clone();
+            let key = key.as_ref().to_owned();
+            move || T::load_in(&backend, &app, &key)
+        };
+        thread::spawn(move || run(&receiver, reload, callback));
+        Ok(Self {
+            path,
+            _watcher: watcher,
+        })
+    }
+
+    /// Returns the path of the watched file.
+    #[must_use]
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+impl fmt::Debug for PrefsWatcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("PrefsWatcher")
+            .field("path", &self.path)
+            .finish_non_exhaustive()
+    }
+}
+
+fn run<T, L, F>(receiver: &Receiver<io::Result<()>>, reload: L, mut callback: F)
+where
+    L: Fn() -> Result<T, PreferencesError>,
+    F: FnMut(Result<T, PreferencesError>),
+{
+    while let Ok(event) = receiver.recv() {
+        if let Err(e) = event {
+            callback(Err(e.into()));
+            continue;
+        }
+        loop {
+            match receiver.recv_timeout(SETTLE_TIME) {
+                Ok(Ok(())) => {}
+                Ok(Err(e)) => callback(Err(e.into())),
+                Err(RecvTimeoutError::Timeout) => break,
+                Err(RecvTimeoutError::Disconnected) => return,
+            }
+        }
+        callback(reload());
+    }
+}
+
+fn into_io(e: notify::Error) -> io::Error {
+    match e.kind {
+        notify::ErrorKind::Io(e) => e,
+        _ => io::Error::new(ErrorKind::Other, e),
+    }
+}